@@ -2,8 +2,8 @@
 
 set -ex
 
-cargo build  --features="$FEATURES"
-cargo test   --features="$FEATURES"
-cargo clippy --features="$FEATURES" -- -D clippy::all
-cargo run    --features="$FEATURES" -- --help
+cargo build  --no-default-features --features="$FEATURES"
+cargo test   --no-default-features --features="$FEATURES"
+cargo clippy --no-default-features --features="$FEATURES" -- -D clippy::all
+cargo run    --no-default-features --features="$FEATURES" -- --help
 cargo fmt                           -- --check
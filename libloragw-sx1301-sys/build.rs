@@ -1,11 +1,30 @@
 extern crate cc;
 
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Prefix applied to every global symbol defined by the vendored
+/// `libloragw`.
+///
+/// Both SX1301 and SX1302 HALs define the same `lgw_*` functions and
+/// a large number of unprefixed c globals. Namespacing our copy
+/// allows it to be linked into the same binary as the SX1302 HAL.
+/// `src/bindings.rs` refers to each function by its prefixed
+/// `link_name`.
+const SYMBOL_PREFIX: &str = "sx1301_";
+
+const LIB_NAME: &str = "loragw_sx1301";
+
 fn main() {
     // Build our extracted, modified, and vendored `libloragw`.
     //
     // The origial source can be found at
     // https://github.com/Lora-net/lora_gateway
-    cc::Build::new()
+    let mut build = cc::Build::new();
+    build
         .file("vendor/libloragw/loragw_aux.c")
         .file("vendor/libloragw/loragw_fpga.c")
         .file("vendor/libloragw/loragw_gps.c")
@@ -14,6 +33,67 @@ fn main() {
         .file("vendor/libloragw/loragw_radio.c")
         .file("vendor/libloragw/loragw_reg.c")
         .file("vendor/libloragw/loragw_spi.native.c")
+        // Tentative definitions would otherwise become common
+        // symbols, which the linker silently merges across
+        // libraries.
+        .flag_if_supported("-fno-common")
         .static_flag(true)
-        .compile("loragw");
+        .compile(LIB_NAME);
+
+    let compiler = build.get_compiler();
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let lib = out_dir.join(format!("lib{}.a", LIB_NAME));
+
+    // Rename every global symbol defined in the archive, as well as
+    // all references to them.
+    let nm = binutil(compiler.path(), "nm", "NM");
+    let output = Command::new(&nm)
+        .arg("--defined-only")
+        .arg("--extern-only")
+        .arg("--format=posix")
+        .arg(&lib)
+        .output()
+        .unwrap_or_else(|e| panic!("failed to run {:?}: {}", nm, e));
+    assert!(output.status.success(), "{:?} failed", nm);
+    let mut redefs = String::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        // `--format=posix` lines are `NAME TYPE [VALUE SIZE]`, while
+        // archive member headers end with a colon.
+        let mut fields = line.split_whitespace();
+        if let (Some(name), Some(_)) = (fields.next(), fields.next()) {
+            if !name.ends_with(':') {
+                redefs.push_str(&format!("{} {}{}\n", name, SYMBOL_PREFIX, name));
+            }
+        }
+    }
+    let redefs_path = out_dir.join("redefine-syms.txt");
+    fs::write(&redefs_path, redefs).unwrap();
+
+    let objcopy = binutil(compiler.path(), "objcopy", "OBJCOPY");
+    let status = Command::new(&objcopy)
+        .arg(format!("--redefine-syms={}", redefs_path.display()))
+        .arg(&lib)
+        .status()
+        .unwrap_or_else(|e| panic!("failed to run {:?}: {}", objcopy, e));
+    assert!(status.success(), "{:?} failed", objcopy);
+}
+
+/// Returns the binutils `tool` matching the c compiler, e.g.
+/// `aarch64-linux-gnu-nm` for `aarch64-linux-gnu-gcc`. `env_var`
+/// overrides the detected path.
+fn binutil(compiler: &Path, tool: &str, env_var: &str) -> PathBuf {
+    println!("cargo:rerun-if-env-changed={}", env_var);
+    if let Ok(path) = env::var(env_var) {
+        return PathBuf::from(path);
+    }
+    let name = compiler
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+    match name.rfind('-') {
+        Some(idx) if name.ends_with("gcc") => {
+            compiler.with_file_name(format!("{}{}", &name[..=idx], tool))
+        }
+        _ => PathBuf::from(tool),
+    }
 }
@@ -1,21 +1,41 @@
 extern crate cc;
 
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Prefix applied to every global symbol defined by the vendored
+/// `sx1302_hal`.
+///
+/// Both SX1301 and SX1302 HALs define the same `lgw_*` functions and
+/// a large number of unprefixed c globals. Namespacing our copy
+/// allows it to be linked into the same binary as the SX1301 HAL.
+/// `src/bindings.rs` refers to each function by its prefixed
+/// `link_name`.
+const SYMBOL_PREFIX: &str = "sx1302_";
+
 fn main() {
     // Build `libtinymt32` (mersenne twister) which `libloragw` depends on.
-    cc::Build::new()
+    let mut tinymt32 = cc::Build::new();
+    tinymt32
         .include("vendor/sx1302_hal/libtools/inc")
         .file("vendor/sx1302_hal/libtools/src/tinymt32.c")
+        .flag_if_supported("-fno-common")
         .static_flag(true)
-        .compile("tinymt32");
+        .compile("loragw_sx1302_tinymt32");
 
     // Build our extracted, modified, and vendored `libloragw`.
-    cc::Build::new()
+    let mut loragw = cc::Build::new();
+    loragw
         .include("vendor/sx1302_hal/libloragw/inc")
         .include("vendor/sx1302_hal/libtools/inc")
         .include("vendor/sx1302_hal_cfg")
         .file("vendor/sx1302_hal/libloragw/src/loragw_aux.c")
         .file("vendor/sx1302_hal/libloragw/src/loragw_cal.c")
         .file("vendor/sx1302_hal/libloragw/src/loragw_debug.c")
+        .file("vendor/sx1302_hal/libloragw/src/loragw_gps.c")
         .file("vendor/sx1302_hal/libloragw/src/loragw_hal.c")
         .file("vendor/sx1302_hal/libloragw/src/loragw_i2c.c")
         .file("vendor/sx1302_hal/libloragw/src/loragw_reg.c")
@@ -26,6 +46,74 @@ fn main() {
         .file("vendor/sx1302_hal/libloragw/src/loragw_sx1302.c")
         .file("vendor/sx1302_hal/libloragw/src/loragw_sx1302_rx.c")
         .file("vendor/sx1302_hal/libloragw/src/loragw_sx1302_timestamp.c")
+        // Tentative definitions would otherwise become common
+        // symbols, which the linker silently merges across
+        // libraries.
+        .flag_if_supported("-fno-common")
         .static_flag(true)
-        .compile("loragw");
+        .compile("loragw_sx1302");
+
+    // `libloragw` references `libtinymt32`, so both archives are
+    // renamed with the union of their symbols.
+    let compiler = loragw.get_compiler();
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let libs = [
+        out_dir.join("libloragw_sx1302_tinymt32.a"),
+        out_dir.join("libloragw_sx1302.a"),
+    ];
+
+    let nm = binutil(compiler.path(), "nm", "NM");
+    let mut redefs = String::new();
+    for lib in &libs {
+        let output = Command::new(&nm)
+            .arg("--defined-only")
+            .arg("--extern-only")
+            .arg("--format=posix")
+            .arg(lib)
+            .output()
+            .unwrap_or_else(|e| panic!("failed to run {:?}: {}", nm, e));
+        assert!(output.status.success(), "{:?} failed", nm);
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            // `--format=posix` lines are `NAME TYPE [VALUE SIZE]`,
+            // while archive member headers end with a colon.
+            let mut fields = line.split_whitespace();
+            if let (Some(name), Some(_)) = (fields.next(), fields.next()) {
+                if !name.ends_with(':') {
+                    redefs.push_str(&format!("{} {}{}\n", name, SYMBOL_PREFIX, name));
+                }
+            }
+        }
+    }
+    let redefs_path = out_dir.join("redefine-syms.txt");
+    fs::write(&redefs_path, redefs).unwrap();
+
+    let objcopy = binutil(compiler.path(), "objcopy", "OBJCOPY");
+    for lib in &libs {
+        let status = Command::new(&objcopy)
+            .arg(format!("--redefine-syms={}", redefs_path.display()))
+            .arg(lib)
+            .status()
+            .unwrap_or_else(|e| panic!("failed to run {:?}: {}", objcopy, e));
+        assert!(status.success(), "{:?} failed", objcopy);
+    }
+}
+
+/// Returns the binutils `tool` matching the c compiler, e.g.
+/// `aarch64-linux-gnu-nm` for `aarch64-linux-gnu-gcc`. `env_var`
+/// overrides the detected path.
+fn binutil(compiler: &Path, tool: &str, env_var: &str) -> PathBuf {
+    println!("cargo:rerun-if-env-changed={}", env_var);
+    if let Ok(path) = env::var(env_var) {
+        return PathBuf::from(path);
+    }
+    let name = compiler
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+    match name.rfind('-') {
+        Some(idx) if name.ends_with("gcc") => {
+            compiler.with_file_name(format!("{}{}", &name[..=idx], tool))
+        }
+        _ => PathBuf::from(tool),
+    }
 }
@@ -6,6 +6,11 @@ pub type __uint16_t = ::std::os::raw::c_ushort;
 pub type __int32_t = ::std::os::raw::c_int;
 pub type __uint32_t = ::std::os::raw::c_uint;
 pub type __uint64_t = ::std::os::raw::c_ulong;
+pub type __time_t = ::std::os::raw::c_long;
+pub type __syscall_slong_t = ::std::os::raw::c_long;
+pub type size_t = ::std::os::raw::c_ulong;
+pub type time_t = __time_t;
+pub type speed_t = ::std::os::raw::c_uint;
 pub const lgw_radio_type_t_LGW_RADIO_TYPE_NONE: lgw_radio_type_t = 0;
 pub const lgw_radio_type_t_LGW_RADIO_TYPE_SX1255: lgw_radio_type_t = 1;
 pub const lgw_radio_type_t_LGW_RADIO_TYPE_SX1257: lgw_radio_type_t = 2;
@@ -1046,6 +1051,7 @@ extern "C" {
     #[doc = "@brief Configure the gateway board"]
     #[doc = "@param conf structure containing the configuration parameters"]
     #[doc = "@return LGW_HAL_ERROR id the operation failed, LGW_HAL_SUCCESS else"]
+    #[link_name = "sx1302_lgw_board_setconf"]
     pub fn lgw_board_setconf(conf: *mut lgw_conf_board_s) -> ::std::os::raw::c_int;
 }
 extern "C" {
@@ -1053,6 +1059,7 @@ extern "C" {
     #[doc = "@param rf_chain number of the RF chain to configure [0, LGW_RF_CHAIN_NB - 1]"]
     #[doc = "@param conf structure containing the configuration parameters"]
     #[doc = "@return LGW_HAL_ERROR id the operation failed, LGW_HAL_SUCCESS else"]
+    #[link_name = "sx1302_lgw_rxrf_setconf"]
     pub fn lgw_rxrf_setconf(rf_chain: u8, conf: *mut lgw_conf_rxrf_s) -> ::std::os::raw::c_int;
 }
 extern "C" {
@@ -1060,22 +1067,26 @@ extern "C" {
     #[doc = "@param if_chain number of the IF chain + modem to configure [0, LGW_IF_CHAIN_NB - 1]"]
     #[doc = "@param conf structure containing the configuration parameters"]
     #[doc = "@return LGW_HAL_ERROR id the operation failed, LGW_HAL_SUCCESS else"]
+    #[link_name = "sx1302_lgw_rxif_setconf"]
     pub fn lgw_rxif_setconf(if_chain: u8, conf: *mut lgw_conf_rxif_s) -> ::std::os::raw::c_int;
 }
 extern "C" {
     #[doc = "@brief Configure the Tx gain LUT"]
     #[doc = "@param pointer to structure defining the LUT"]
     #[doc = "@return LGW_HAL_ERROR id the operation failed, LGW_HAL_SUCCESS else"]
+    #[link_name = "sx1302_lgw_txgain_setconf"]
     pub fn lgw_txgain_setconf(rf_chain: u8, conf: *mut lgw_tx_gain_lut_s) -> ::std::os::raw::c_int;
 }
 extern "C" {
     #[doc = "@brief Connect to the LoRa concentrator, reset it and configure it according to previously set parameters"]
     #[doc = "@return LGW_HAL_ERROR id the operation failed, LGW_HAL_SUCCESS else"]
+    #[link_name = "sx1302_lgw_start"]
     pub fn lgw_start() -> ::std::os::raw::c_int;
 }
 extern "C" {
     #[doc = "@brief Stop the LoRa concentrator and disconnect it"]
     #[doc = "@return LGW_HAL_ERROR id the operation failed, LGW_HAL_SUCCESS else"]
+    #[link_name = "sx1302_lgw_stop"]
     pub fn lgw_stop() -> ::std::os::raw::c_int;
 }
 extern "C" {
@@ -1083,6 +1094,7 @@ extern "C" {
     #[doc = "@param max_pkt maximum number of packet that must be retrieved (equal to the size of the array of struct)"]
     #[doc = "@param pkt_data pointer to an array of struct that will receive the packet metadata and payload pointers"]
     #[doc = "@return LGW_HAL_ERROR id the operation failed, else the number of packets retrieved"]
+    #[link_name = "sx1302_lgw_receive"]
     pub fn lgw_receive(max_pkt: u8, pkt_data: *mut lgw_pkt_rx_s) -> ::std::os::raw::c_int;
 }
 extern "C" {
@@ -1107,6 +1119,7 @@ extern "C" {
     #[doc = "trigger signal. Because there is no way to anticipate the triggering event and"]
     #[doc = "start the analog circuitry beforehand, that delay must be taken into account in"]
     #[doc = "the protocol."]
+    #[link_name = "sx1302_lgw_send"]
     pub fn lgw_send(pkt_data: *mut lgw_pkt_tx_s) -> ::std::os::raw::c_int;
 }
 extern "C" {
@@ -1114,33 +1127,380 @@ extern "C" {
     #[doc = "@param select is used to select what status we want to know"]
     #[doc = "@param code is used to return the status code"]
     #[doc = "@return LGW_HAL_ERROR id the operation failed, LGW_HAL_SUCCESS else"]
+    #[link_name = "sx1302_lgw_status"]
     pub fn lgw_status(rf_chain: u8, select: u8, code: *mut u8) -> ::std::os::raw::c_int;
 }
 extern "C" {
     #[doc = "@brief Abort a currently scheduled or ongoing TX"]
     #[doc = "@return LGW_HAL_ERROR id the operation failed, LGW_HAL_SUCCESS else"]
+    #[link_name = "sx1302_lgw_abort_tx"]
     pub fn lgw_abort_tx(rf_chain: u8) -> ::std::os::raw::c_int;
 }
 extern "C" {
     #[doc = "@brief Return value of internal counter when latest event (eg GPS pulse) was captured"]
     #[doc = "@param trig_cnt_us pointer to receive timestamp value"]
     #[doc = "@return LGW_HAL_ERROR id the operation failed, LGW_HAL_SUCCESS else"]
+    #[link_name = "sx1302_lgw_get_trigcnt"]
     pub fn lgw_get_trigcnt(trig_cnt_us: *mut u32) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    #[doc = "@brief Return instateneous value of internal counter"]
+    #[doc = "@param inst_cnt_us pointer to receive timestamp value"]
+    #[doc = "@return LGW_HAL_ERROR id the operation failed, LGW_HAL_SUCCESS else"]
+    #[link_name = "sx1302_lgw_get_instcnt"]
+    pub fn lgw_get_instcnt(inst_cnt_us: *mut u32) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = "@brief Return the temperature measured by the LoRa concentrator sensor"]
+    #[doc = "@param temperature The temperature measured, in degree celcius"]
+    #[doc = "@return LGW_HAL_ERROR id the operation failed, LGW_HAL_SUCCESS else"]
+    #[link_name = "sx1302_lgw_get_temperature"]
+    pub fn lgw_get_temperature(temperature: *mut f32) -> ::std::os::raw::c_int;
+}
 extern "C" {
     #[doc = "@brief Allow user to check the version/options of the library once compiled"]
     #[doc = "@return pointer on a human-readable null terminated string"]
+    #[link_name = "sx1302_lgw_version_info"]
     pub fn lgw_version_info() -> *const ::std::os::raw::c_char;
 }
 extern "C" {
     #[doc = "@brief Return time on air of given packet, in milliseconds"]
     #[doc = "@param packet is a pointer to the packet structure"]
     #[doc = "@return the packet time on air in milliseconds"]
+    #[link_name = "sx1302_lgw_time_on_air"]
     pub fn lgw_time_on_air(packet: *mut lgw_pkt_tx_s) -> u32;
 }
 extern "C" {
     #[doc = "@brief Connect LoRa concentrator by opening SPI link"]
     #[doc = "@param spidev_path path to the SPI device to be used to connect to the SX1302"]
     #[doc = "@return status of register operation (LGW_REG_SUCCESS/LGW_REG_ERROR)"]
+    #[link_name = "sx1302_lgw_connect"]
     pub fn lgw_connect(spidev_path: *const ::std::os::raw::c_char) -> ::std::os::raw::c_int;
 }
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct timespec {
+    pub tv_sec: __time_t,
+    pub tv_nsec: __syscall_slong_t,
+}
+#[test]
+fn bindgen_test_layout_timespec() {
+    assert_eq!(
+        ::std::mem::size_of::<timespec>(),
+        16usize,
+        concat!("Size of: ", stringify!(timespec))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<timespec>(),
+        8usize,
+        concat!("Alignment of ", stringify!(timespec))
+    );
+    assert_eq!(
+        unsafe { &(*(::std::ptr::null::<timespec>())).tv_sec as *const _ as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(timespec),
+            "::",
+            stringify!(tv_sec)
+        )
+    );
+    assert_eq!(
+        unsafe { &(*(::std::ptr::null::<timespec>())).tv_nsec as *const _ as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(timespec),
+            "::",
+            stringify!(tv_nsec)
+        )
+    );
+}
+#[doc = "@struct coord_s"]
+#[doc = "@brief Time solution required for timestamp to absolute time conversion"]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct tref {
+    pub systime: time_t,
+    #[doc = "> system time when solution was calculated"]
+    pub count_us: u32,
+    #[doc = "> reference concentrator internal timestamp"]
+    pub utc: timespec,
+    #[doc = "> reference UTC time (from GPS/NMEA)"]
+    pub gps: timespec,
+    #[doc = "> reference GPS time (since 01.Jan.1980)"]
+    pub xtal_err: f64,
+}
+#[test]
+fn bindgen_test_layout_tref() {
+    assert_eq!(
+        ::std::mem::size_of::<tref>(),
+        56usize,
+        concat!("Size of: ", stringify!(tref))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<tref>(),
+        8usize,
+        concat!("Alignment of ", stringify!(tref))
+    );
+    assert_eq!(
+        unsafe { &(*(::std::ptr::null::<tref>())).systime as *const _ as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(tref),
+            "::",
+            stringify!(systime)
+        )
+    );
+    assert_eq!(
+        unsafe { &(*(::std::ptr::null::<tref>())).count_us as *const _ as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(tref),
+            "::",
+            stringify!(count_us)
+        )
+    );
+    assert_eq!(
+        unsafe { &(*(::std::ptr::null::<tref>())).utc as *const _ as usize },
+        16usize,
+        concat!("Offset of field: ", stringify!(tref), "::", stringify!(utc))
+    );
+    assert_eq!(
+        unsafe { &(*(::std::ptr::null::<tref>())).gps as *const _ as usize },
+        32usize,
+        concat!("Offset of field: ", stringify!(tref), "::", stringify!(gps))
+    );
+    assert_eq!(
+        unsafe { &(*(::std::ptr::null::<tref>())).xtal_err as *const _ as usize },
+        48usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(tref),
+            "::",
+            stringify!(xtal_err)
+        )
+    );
+}
+#[doc = "@struct coord_s"]
+#[doc = "@brief Geodesic coordinates"]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct coord_s {
+    pub lat: f64,
+    #[doc = "> latitude [-90,90] (North +, South -)"]
+    pub lon: f64,
+    #[doc = "> longitude [-180,180] (East +, West -)"]
+    pub alt: ::std::os::raw::c_short,
+}
+#[test]
+fn bindgen_test_layout_coord_s() {
+    assert_eq!(
+        ::std::mem::size_of::<coord_s>(),
+        24usize,
+        concat!("Size of: ", stringify!(coord_s))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<coord_s>(),
+        8usize,
+        concat!("Alignment of ", stringify!(coord_s))
+    );
+    assert_eq!(
+        unsafe { &(*(::std::ptr::null::<coord_s>())).lat as *const _ as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(coord_s),
+            "::",
+            stringify!(lat)
+        )
+    );
+    assert_eq!(
+        unsafe { &(*(::std::ptr::null::<coord_s>())).lon as *const _ as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(coord_s),
+            "::",
+            stringify!(lon)
+        )
+    );
+    assert_eq!(
+        unsafe { &(*(::std::ptr::null::<coord_s>())).alt as *const _ as usize },
+        16usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(coord_s),
+            "::",
+            stringify!(alt)
+        )
+    );
+}
+pub const gps_msg_UNKNOWN: gps_msg = 0;
+#[doc = "> neutral value"]
+pub const gps_msg_IGNORED: gps_msg = 1;
+#[doc = "> frame was not parsed by the system"]
+pub const gps_msg_INVALID: gps_msg = 2;
+#[doc = "> system try to parse frame but failed"]
+pub const gps_msg_INCOMPLETE: gps_msg = 3;
+#[doc = "> frame parsed was missing bytes"]
+pub const gps_msg_NMEA_RMC: gps_msg = 4;
+#[doc = "> Recommended Minimum data (time + date)"]
+pub const gps_msg_NMEA_GGA: gps_msg = 5;
+#[doc = "> Global positioning system fix data (pos + alt)"]
+pub const gps_msg_NMEA_GNS: gps_msg = 6;
+#[doc = "> GNSS fix data (pos + alt, sat number)"]
+pub const gps_msg_NMEA_ZDA: gps_msg = 7;
+#[doc = "> Time and Date"]
+pub const gps_msg_NMEA_GBS: gps_msg = 8;
+#[doc = "> GNSS Satellite Fault Detection"]
+pub const gps_msg_NMEA_GST: gps_msg = 9;
+#[doc = "> GNSS Pseudo Range Error Statistics"]
+pub const gps_msg_NMEA_GSA: gps_msg = 10;
+#[doc = "> GNSS DOP and Active Satellites (sat number)"]
+pub const gps_msg_NMEA_GSV: gps_msg = 11;
+#[doc = "> GNSS Satellites in View (sat SNR)"]
+pub const gps_msg_NMEA_GLL: gps_msg = 12;
+#[doc = "> Latitude and longitude, with time fix and status"]
+pub const gps_msg_NMEA_TXT: gps_msg = 13;
+#[doc = "> Text Transmission"]
+pub const gps_msg_NMEA_VTG: gps_msg = 14;
+#[doc = "> Course over ground and Ground speed"]
+pub const gps_msg_UBX_NAV_TIMEGPS: gps_msg = 15;
+#[doc = "> GPS Time Solution"]
+pub const gps_msg_UBX_NAV_TIMEUTC: gps_msg = 16;
+#[doc = "> UTC Time Solution"]
+#[doc = "@enum gps_msg"]
+#[doc = "@brief Type of GPS (and other GNSS) sentences"]
+pub type gps_msg = u32;
+extern "C" {
+    #[doc = "@brief Configure a GPS module"]
+    #[doc = ""]
+    #[doc = "@param tty_path path to the TTY connected to the GPS"]
+    #[doc = "@param gps_familly parameter (eg. ubx6 for uBlox gen.6)"]
+    #[doc = "@param target_brate target baudrate for communication (0 keeps default target baudrate)"]
+    #[doc = "@param fd_ptr pointer to a variable to receive file descriptor on GPS tty"]
+    #[doc = "@return success if the function was able to connect and configure a GPS module"]
+    #[link_name = "sx1302_lgw_gps_enable"]
+    pub fn lgw_gps_enable(
+        tty_path: *mut ::std::os::raw::c_char,
+        gps_familly: *mut ::std::os::raw::c_char,
+        target_brate: speed_t,
+        fd_ptr: *mut ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = "@brief Restore GPS serial configuration and close serial device"]
+    #[doc = ""]
+    #[doc = "@param fd file descriptor on GPS tty"]
+    #[doc = "@return success if the function was able to complete"]
+    #[link_name = "sx1302_lgw_gps_disable"]
+    pub fn lgw_gps_disable(fd: ::std::os::raw::c_int) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = "@brief Parse messages coming from the GPS system (or other GNSS)"]
+    #[doc = ""]
+    #[doc = "@param serial_buff pointer to the string to be parsed"]
+    #[doc = "@param buff_size maximum string lengths for NMEA parsing (incl. null char)"]
+    #[doc = "@return type of frame parsed"]
+    #[doc = ""]
+    #[doc = "The RAW NMEA sentences are parsed to a global set of variables shared with the"]
+    #[doc = "lgw_gps_get function."]
+    #[doc = "If the lgw_parse_nmea and lgw_gps_get are used in different threads, a mutex"]
+    #[doc = "lock must be acquired before calling either function."]
+    #[link_name = "sx1302_lgw_parse_nmea"]
+    pub fn lgw_parse_nmea(
+        serial_buff: *const ::std::os::raw::c_char,
+        buff_size: ::std::os::raw::c_int,
+    ) -> gps_msg;
+}
+extern "C" {
+    #[doc = "@brief Parse Ublox proprietary messages coming from the GPS system"]
+    #[doc = ""]
+    #[doc = "@param serial_buff pointer to the string to be parsed"]
+    #[doc = "@param buff_size maximum string lengths for UBX parsing (incl. null char)"]
+    #[doc = "@param msg_size number of bytes parsed as UBX message if found"]
+    #[doc = "@return type of frame parsed"]
+    #[doc = ""]
+    #[doc = "The RAW UBX sentences are parsed to a global set of variables shared with the"]
+    #[doc = "lgw_gps_get function."]
+    #[doc = "If the lgw_parse_ubx and lgw_gps_get are used in different threads, a mutex"]
+    #[doc = "lock must be acquired before calling either function."]
+    #[link_name = "sx1302_lgw_parse_ubx"]
+    pub fn lgw_parse_ubx(
+        serial_buff: *const ::std::os::raw::c_char,
+        buff_size: size_t,
+        msg_size: *mut size_t,
+    ) -> gps_msg;
+}
+extern "C" {
+    #[doc = "@brief Get the GPS solution (space & time) for the concentrator"]
+    #[doc = ""]
+    #[doc = "@param utc pointer to store UTC time, with ns precision (NULL to ignore)"]
+    #[doc = "@param gps_time pointer to store GPS time, with ns precision (NULL to ignore)"]
+    #[doc = "@param loc pointer to store coordinates (NULL to ignore)"]
+    #[doc = "@param err pointer to store coordinates standard deviation (NULL to ignore)"]
+    #[doc = "@return success if the chosen elements could be returned"]
+    #[doc = ""]
+    #[doc = "This function read the global variables generated by the NMEA/UBX parsing"]
+    #[doc = "functions lgw_parse_nmea/lgw_parse_ubx. It returns time and location data in a"]
+    #[doc = "format that is exploitable by other functions in that library sub-module."]
+    #[doc = "If the lgw_parse_nmea/lgw_parse_ubx and lgw_gps_get are used in different"]
+    #[doc = "threads, a mutex lock must be acquired before calling either function."]
+    #[link_name = "sx1302_lgw_gps_get"]
+    pub fn lgw_gps_get(
+        utc: *mut timespec,
+        gps_time: *mut timespec,
+        loc: *mut coord_s,
+        err: *mut coord_s,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = "@brief Get time and position information from the serial GPS last message received"]
+    #[doc = "@param utc UTC time, with ns precision (leap seconds are ignored)"]
+    #[doc = "@param gps_time timestamp of last time pulse from the GPS module converted to the UNIX epoch"]
+    #[doc = "(leap seconds are ignored)"]
+    #[doc = "@param loc location information"]
+    #[doc = "@param err location error estimate if supported"]
+    #[doc = "@return success if timestamp was read and time reference could be refreshed"]
+    #[doc = ""]
+    #[doc = "Set systime to 0 in ref to trigger initial synchronization."]
+    #[link_name = "sx1302_lgw_gps_sync"]
+    pub fn lgw_gps_sync(
+        ref_: *mut tref,
+        count_us: u32,
+        utc: timespec,
+        gps_time: timespec,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = "@brief Convert concentrator timestamp counter value to UTC time"]
+    #[doc = ""]
+    #[doc = "@param ref time reference structure required for time conversion"]
+    #[doc = "@param count_us internal timestamp counter of the LoRa concentrator"]
+    #[doc = "@param utc pointer to store UTC time, with ns precision (leap seconds ignored)"]
+    #[doc = "@return success if the function was able to convert timestamp to UTC"]
+    #[doc = ""]
+    #[doc = "This function is typically used when a packet is received to transform the"]
+    #[doc = "internal counter-based timestamp in an absolute timestamp with an accuracy in"]
+    #[doc = "the order of a couple microseconds (ns resolution)."]
+    #[link_name = "sx1302_lgw_cnt2utc"]
+    pub fn lgw_cnt2utc(ref_: tref, count_us: u32, utc: *mut timespec) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = "@brief Convert concentrator timestamp counter value to GPS time"]
+    #[doc = ""]
+    #[doc = "@param ref time reference structure required for time conversion"]
+    #[doc = "@param count_us internal timestamp counter of the LoRa concentrator"]
+    #[doc = "@param gps_time pointer to store GPS time, with ns precision (leap seconds ignored)"]
+    #[doc = "@return success if the function was able to convert timestamp to GPS time"]
+    #[doc = ""]
+    #[doc = "This function is typically used when a packet is received to transform the"]
+    #[doc = "internal counter-based timestamp in an absolute timestamp with an accuracy in"]
+    #[doc = "the order of a millisecond."]
+    #[link_name = "sx1302_lgw_cnt2gps"]
+    pub fn lgw_cnt2gps(ref_: tref, count_us: u32, gps_time: *mut timespec)
+        -> ::std::os::raw::c_int;
+}
@@ -43,6 +43,9 @@ impl LongFi {
                 msg::RadioResp_oneof_kind::tx(_) => self.sender.tx_resp(),
                 msg::RadioResp_oneof_kind::rx_packet(rx) => self.parser.parse(rx),
                 msg::RadioResp_oneof_kind::parse_err(_) => None,
+                msg::RadioResp_oneof_kind::cancel(_) => None,
+                msg::RadioResp_oneof_kind::counter(_) => None,
+                msg::RadioResp_oneof_kind::status(_) => None,
             },
             None => None,
         }
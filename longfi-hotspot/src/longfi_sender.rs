@@ -27,8 +27,7 @@ impl LongFiSender {
             Some(fragment) => {
                 debug!("[LongFi] Sending another fragment. {} remaining", vec.len());
                 // print the fragment payload nicely
-                if let Some(msg) = &fragment.kind {
-                    let msg::RadioReq_oneof_kind::tx(tx) = msg;
+                if let Some(msg::RadioReq_oneof_kind::tx(tx)) = &fragment.kind {
                     debug!("[LongFi] Fragment: {:?}", tx.payload);
                 }
 
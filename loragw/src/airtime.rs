@@ -0,0 +1,235 @@
+//! Pure-Rust time-on-air calculations.
+//!
+//! These mirror `lgw_time_on_air` from the concentrator HALs, but
+//! don't require an open `Concentrator` and keep microsecond
+//! precision (the HALs return whole milliseconds).
+
+use crate::{Bandwidth, Coderate, Spreading, TxPacket, TxPacketFSK, TxPacketLoRa};
+use std::time::Duration;
+
+/// Preamble length used by the HALs when none is specified.
+const STD_LORA_PREAMBLE: u16 = 8;
+/// Shortest preamble the HALs will transmit.
+const MIN_LORA_PREAMBLE: u16 = 6;
+/// Preamble length (in bytes) used by the HALs when none is specified.
+const STD_FSK_PREAMBLE: u16 = 5;
+/// Shortest preamble (in bytes) the HALs will transmit.
+const MIN_FSK_PREAMBLE: u16 = 3;
+/// Default FSK sync word size, in bytes.
+const FSK_SYNC_WORD_SIZE: u32 = 3;
+
+/// Returns the preamble length the HAL will actually transmit with.
+fn effective_preamble(preamble: Option<u16>, std: u16, min: u16) -> u16 {
+    match preamble {
+        None | Some(0) => std,
+        Some(len) => len.max(min),
+    }
+}
+
+impl TxPacket {
+    /// Returns the time it will take to transmit this packet.
+    ///
+    /// A zero duration is returned for packets with an undefined
+    /// bandwidth or spreading factor, as does the HAL.
+    pub fn time_on_air(&self) -> Duration {
+        match self {
+            TxPacket::LoRa(pkt) => pkt.time_on_air(),
+            TxPacket::FSK(pkt) => pkt.time_on_air(),
+        }
+    }
+}
+
+impl TxPacketLoRa {
+    /// Returns the time it will take to transmit this packet.
+    ///
+    /// A zero duration is returned for packets with an undefined
+    /// bandwidth or spreading factor, as does the HAL.
+    pub fn time_on_air(&self) -> Duration {
+        let bw_hz = match self.bandwidth {
+            Bandwidth::Undefined => {
+                log::error!("can't compute time on air with undefined bandwidth");
+                return Duration::from_secs(0);
+            }
+            Bandwidth::BW500kHz => 500_000.0,
+            Bandwidth::BW250kHz => 250_000.0,
+            Bandwidth::BW125kHz => 125_000.0,
+            Bandwidth::BW62_5kHz => 62_500.0,
+            Bandwidth::BW31_2kHz => 31_250.0,
+            Bandwidth::BW15_6kHz => 15_625.0,
+            Bandwidth::BW7_8kHz => 7_812.5,
+        };
+        let sf: i32 = match self.spreading {
+            Spreading::Undefined | Spreading::Multi => {
+                log::error!(
+                    "can't compute time on air with {:?} spreading",
+                    self.spreading
+                );
+                return Duration::from_secs(0);
+            }
+            Spreading::SF5 => 5,
+            Spreading::SF6 => 6,
+            Spreading::SF7 => 7,
+            Spreading::SF8 => 8,
+            Spreading::SF9 => 9,
+            Spreading::SF10 => 10,
+            Spreading::SF11 => 11,
+            Spreading::SF12 => 12,
+        };
+        let cr = match self.coderate {
+            Coderate::Undefined => 0,
+            Coderate::Cr4_5 => 1,
+            Coderate::Cr4_6 => 2,
+            Coderate::Cr4_7 => 3,
+            Coderate::Cr4_8 => 4,
+        };
+        let preamble = effective_preamble(self.preamble, STD_LORA_PREAMBLE, MIN_LORA_PREAMBLE);
+
+        let t_sym_us = f64::from(1u32 << sf) / bw_hz * 1e6;
+
+        // Like the HALs, low datarate optimization is assumed for
+        // SF11 and SF12 regardless of bandwidth.
+        let de = if sf >= 11 { 1 } else { 0 };
+        let header = if self.implicit_header { 0 } else { 20 };
+        // Like the SX1301 HAL, the 16 CRC bits are counted even when
+        // `omit_crc` is set, which the JIT queue and regulator rely
+        // on to agree with it.
+        let crc = 16;
+        // SF5 and SF6 have no extra sync symbols and a longer preamble
+        // detection overhead.
+        let (sync_bits, preamble_extra) = if sf >= 7 { (8, 4.25) } else { (0, 6.25) };
+
+        let payload_bits = 8 * self.payload.len() as i32 + crc - 4 * sf + sync_bits + header;
+        let payload_symbols = 8.0
+            + (f64::from(payload_bits.max(0)) / f64::from(4 * (sf - 2 * de))).ceil()
+                * f64::from(cr + 4);
+        let symbols = f64::from(preamble) + preamble_extra + payload_symbols;
+
+        Duration::from_nanos((symbols * t_sym_us * 1e3) as u64)
+    }
+}
+
+impl TxPacketFSK {
+    /// Returns the time it will take to transmit this packet.
+    ///
+    /// A zero duration is returned for packets with a zero datarate.
+    pub fn time_on_air(&self) -> Duration {
+        if self.datarate == 0 {
+            log::error!("can't compute time on air with 0 datarate");
+            return Duration::from_secs(0);
+        }
+        // PREAMBLE + SYNC_WORD + PKT_LEN + PAYLOAD + CRC
+        let bytes = u32::from(effective_preamble(
+            self.preamble,
+            STD_FSK_PREAMBLE,
+            MIN_FSK_PREAMBLE,
+        )) + FSK_SYNC_WORD_SIZE
+            + 1
+            + self.payload.len() as u32
+            + if self.omit_crc { 0 } else { 2 };
+        Duration::from_nanos(u64::from(8 * bytes) * 1_000_000_000 / u64::from(self.datarate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Radio, TxMode};
+
+    fn lora(spreading: Spreading, bandwidth: Bandwidth, len: usize) -> TxPacketLoRa {
+        TxPacketLoRa {
+            bandwidth,
+            spreading,
+            ..crate::sim::test_util::lora(vec![0; len])
+        }
+    }
+
+    #[test]
+    fn test_lora_time_on_air() {
+        let pkt = lora(Spreading::SF7, Bandwidth::BW125kHz, 64);
+        assert_eq!(pkt.time_on_air(), Duration::from_micros(118_016));
+        let pkt = lora(Spreading::SF12, Bandwidth::BW125kHz, 64);
+        assert_eq!(pkt.time_on_air(), Duration::from_micros(2_793_472));
+        let pkt = lora(Spreading::SF10, Bandwidth::BW500kHz, 11);
+        assert_eq!(pkt.time_on_air(), Duration::from_micros(72_192));
+    }
+
+    #[test]
+    fn test_lora_time_on_air_counts_crc() {
+        let mut pkt = lora(Spreading::SF7, Bandwidth::BW125kHz, 64);
+        pkt.omit_crc = true;
+        assert_eq!(pkt.time_on_air(), Duration::from_micros(118_016));
+    }
+
+    #[test]
+    fn test_lora_undefined_time_on_air() {
+        let pkt = lora(Spreading::Multi, Bandwidth::BW125kHz, 10);
+        assert_eq!(pkt.time_on_air(), Duration::from_secs(0));
+        let pkt = lora(Spreading::SF7, Bandwidth::Undefined, 10);
+        assert_eq!(pkt.time_on_air(), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_fsk_time_on_air() {
+        let pkt = TxPacketFSK {
+            freq: 868_800_000,
+            mode: TxMode::Immediate,
+            radio: Radio::R0,
+            power: 0,
+            datarate: 50_000,
+            deviation: 25,
+            preamble: None,
+            omit_crc: false,
+            fixed_len: false,
+            payload: vec![0; 20],
+        };
+        // (5 + 3 + 1 + 20 + 2) bytes at 50 kbps
+        assert_eq!(pkt.time_on_air(), Duration::from_micros(4_960));
+    }
+
+    /// Compares against the SX1301 HAL's `lgw_time_on_air`, which
+    /// truncates to whole milliseconds.
+    #[cfg(feature = "sx1301")]
+    #[test]
+    fn test_lora_time_on_air_matches_sx1301_hal() {
+        use std::convert::TryFrom;
+        let spreadings = [
+            Spreading::SF7,
+            Spreading::SF8,
+            Spreading::SF9,
+            Spreading::SF10,
+            Spreading::SF11,
+            Spreading::SF12,
+        ];
+        let bandwidths = [
+            Bandwidth::BW125kHz,
+            Bandwidth::BW250kHz,
+            Bandwidth::BW500kHz,
+        ];
+        for &spreading in spreadings.iter() {
+            for &bandwidth in bandwidths.iter() {
+                for &(implicit_header, omit_crc) in
+                    [(false, false), (true, false), (false, true)].iter()
+                {
+                    for len in 0..=255 {
+                        let mut pkt = lora(spreading, bandwidth, len);
+                        pkt.implicit_header = implicit_header;
+                        pkt.omit_crc = omit_crc;
+                        // The HAL computes with the preamble as given.
+                        pkt.preamble = Some(STD_LORA_PREAMBLE);
+                        let mut raw = libloragw_sx1301_sys::lgw_pkt_tx_s::try_from(TxPacket::LoRa(
+                            pkt.clone(),
+                        ))
+                        .unwrap();
+                        let hal_ms = unsafe { libloragw_sx1301_sys::lgw_time_on_air(&mut raw) };
+                        assert_eq!(
+                            pkt.time_on_air().as_millis(),
+                            u128::from(hal_ms),
+                            "{:?}",
+                            pkt
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
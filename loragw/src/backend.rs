@@ -0,0 +1,153 @@
+use crate::{
+    regulatory::Region, BoardConf, ChannelConf, Concentrator, LBTConf, Radio, Result, Running,
+    RxPacket, RxRFConf, TxGain, TxPacket, TxStatus, Unconfigured, VersionInfo,
+};
+use std::time;
+
+/// Operations common to all concentrator implementations before
+/// they are started.
+///
+/// Like `ConcentratorBackend`, this allows code above the HAL to be
+/// written once and run against either real hardware or a simulated
+/// board.
+pub trait ConfigurableBackend {
+    /// Configure the gateway board.
+    fn config_board(&self, conf: &BoardConf) -> Result;
+
+    /// Configure an RF chain.
+    fn config_rx_rf(&self, conf: &RxRFConf) -> Result;
+
+    /// Configure an IF chain + modem (must configure before start).
+    fn config_channel(&self, chain: u8, conf: &ChannelConf) -> Result;
+
+    /// Configure the Tx gain LUT of `radio`.
+    fn config_tx_gain(&self, radio: Radio, gains: &[TxGain]) -> Result;
+
+    /// Configure listen-before-talk (must configure before start).
+    fn config_lbt(&self, conf: &LBTConf) -> Result;
+
+    /// Enforce `region`'s duty cycle and dwell time limits on
+    /// transmissions, or lift them with `None`.
+    fn config_region(&self, region: Option<Region>) -> Result;
+
+    /// Start the concentrator according to previously set parameters.
+    fn start(self: Box<Self>) -> Result<Box<dyn ConcentratorBackend>>;
+}
+
+/// Operations common to all concentrator implementations once they
+/// are started.
+///
+/// This allows code above the HAL to be written once and run against
+/// either real hardware (`Concentrator`) or a simulated board
+/// (`SimConcentrator`).
+pub trait ConcentratorBackend {
+    /// Perform a non-blocking read of up to 16 received packets.
+    fn receive(&self) -> Result<Option<Vec<RxPacket>>>;
+
+    /// Transmit `packet` over the air, waiting for the transmitter of
+    /// its radio to be free.
+    fn transmit(&self, packet: TxPacket) -> Result;
+
+    /// Returns the current transmit status of `radio`.
+    fn transmit_status(&self, radio: Radio) -> Result<TxStatus>;
+
+    /// Returns the current value of the concentrator's internal 1 MHz
+    /// counter.
+    fn counter(&self) -> Result<time::Duration>;
+
+    /// Returns the value the internal counter had at the last PPS edge
+    /// from the GPS receiver.
+    fn pps_counter(&self) -> Result<time::Duration>;
+
+    /// Abort any scheduled or in-flight transmission.
+    fn abort_tx(&self) -> Result;
+
+    /// Abort a scheduled or in-flight transmission on `radio` only.
+    fn abort_tx_on(&self, radio: Radio) -> Result;
+
+    /// Returns the board temperature, in degrees Celsius.
+    fn temperature(&self) -> Result<f32>;
+
+    /// Returns the HAL version, and the chip and FPGA versions if
+    /// available.
+    fn version_info(&self) -> Result<VersionInfo>;
+
+    /// Stop the concentrator so it can be reconfigured and started
+    /// again.
+    ///
+    /// Pending transmissions are dropped, and the counter restarts
+    /// from zero on the next start.
+    fn stop(self: Box<Self>) -> Result<Box<dyn ConfigurableBackend>>;
+}
+
+impl ConfigurableBackend for Concentrator<Unconfigured> {
+    fn config_board(&self, conf: &BoardConf) -> Result {
+        Concentrator::config_board(self, conf)
+    }
+
+    fn config_rx_rf(&self, conf: &RxRFConf) -> Result {
+        Concentrator::config_rx_rf(self, conf)
+    }
+
+    fn config_channel(&self, chain: u8, conf: &ChannelConf) -> Result {
+        Concentrator::config_channel(self, chain, conf)
+    }
+
+    fn config_tx_gain(&self, radio: Radio, gains: &[TxGain]) -> Result {
+        Concentrator::config_tx_gain(self, radio, gains)
+    }
+
+    fn config_lbt(&self, conf: &LBTConf) -> Result {
+        Concentrator::config_lbt(self, conf)
+    }
+
+    fn config_region(&self, region: Option<Region>) -> Result {
+        Concentrator::config_region(self, region)
+    }
+
+    fn start(self: Box<Self>) -> Result<Box<dyn ConcentratorBackend>> {
+        Ok(Box::new(Concentrator::start(*self)?))
+    }
+}
+
+impl ConcentratorBackend for Concentrator<Running> {
+    fn receive(&self) -> Result<Option<Vec<RxPacket>>> {
+        Concentrator::receive(self)
+    }
+
+    fn transmit(&self, packet: TxPacket) -> Result {
+        Concentrator::transmit(self, packet)
+    }
+
+    fn transmit_status(&self, radio: Radio) -> Result<TxStatus> {
+        Concentrator::transmit_status(self, radio)
+    }
+
+    fn counter(&self) -> Result<time::Duration> {
+        Concentrator::counter(self)
+    }
+
+    fn pps_counter(&self) -> Result<time::Duration> {
+        Concentrator::pps_counter(self)
+    }
+
+    fn abort_tx(&self) -> Result {
+        Concentrator::abort_tx(self)
+    }
+
+    fn abort_tx_on(&self, radio: Radio) -> Result {
+        Concentrator::abort_tx_on(self, radio)
+    }
+
+    fn temperature(&self) -> Result<f32> {
+        Concentrator::temperature(self)
+    }
+
+    fn version_info(&self) -> Result<VersionInfo> {
+        Concentrator::version_info(self)
+    }
+
+    fn stop(self: Box<Self>) -> Result<Box<dyn ConfigurableBackend>> {
+        Ok(Box::new(Concentrator::stop(*self)?))
+    }
+}
@@ -0,0 +1,100 @@
+//! Extension of the concentrator's counter to 64 bits.
+//!
+//! The concentrator counts microseconds in 32 bits, wrapping roughly
+//! every 71.6 minutes. `Clock` recovers the number of wraps from how
+//! much host time has passed since the last reading, so extended
+//! values keep increasing across wraps, even if the counter was not
+//! read in the meantime.
+
+use crate::error::{Error, Result};
+use std::time::Instant;
+
+/// Largest lead over the counter, in µs, a transmit target may have.
+///
+/// Past this, the target's 32-bit counter value reads as being in the
+/// past to anything comparing counter values across a wrap.
+const MAX_LEAD: u64 = 1 << 31;
+
+/// Tracks the concentrator's counter across wraps.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Clock {
+    /// Most recent extended counter reading, in µs.
+    last: u64,
+    /// Host time `last` was read at.
+    at: Instant,
+}
+
+impl Clock {
+    /// Returns a clock for a counter which is zero now, as it is right
+    /// after the concentrator starts.
+    pub(crate) fn new() -> Self {
+        Clock {
+            last: 0,
+            at: Instant::now(),
+        }
+    }
+
+    /// Extends `count`, a counter value from around now, to 64 bits.
+    ///
+    /// Of all values whose lower 32 bits are `count`, this returns the
+    /// one closest to where host time says the counter is now.
+    pub(crate) fn extend(&self, count: u32) -> u64 {
+        let expected = self.last + self.at.elapsed().as_micros() as u64;
+        let delta = count.wrapping_sub(expected as u32) as i32;
+        (expected as i64 + i64::from(delta)).max(0) as u64
+    }
+
+    /// Records `count`, just read from the counter, and returns it
+    /// extended to 64 bits.
+    pub(crate) fn update(&mut self, count: u32) -> u64 {
+        self.last = self.extend(count);
+        self.at = Instant::now();
+        self.last
+    }
+}
+
+/// Returns the 32-bit counter value to transmit at extended counter
+/// value `target`, when the counter is at `now`.
+///
+/// Fails with `Error::TooLate` if `target` has passed, and
+/// `Error::TooEarly` if it is so far ahead its counter value would be
+/// ambiguous.
+pub(crate) fn tx_count(now: u64, target: u64) -> Result<u32> {
+    if target <= now {
+        log::warn!("TX at {} has passed, counter is at {}", target, now);
+        return Err(Error::TooLate);
+    }
+    if target - now >= MAX_LEAD {
+        log::warn!("TX at {} is too far ahead, counter is at {}", target, now);
+        return Err(Error::TooEarly);
+    }
+    Ok(target as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WRAP: u64 = 1 << 32;
+
+    #[test]
+    fn test_extend_across_wrap() {
+        let mut clock = Clock::new();
+        // As if last read just before the counter wrapped.
+        clock.last = WRAP - 1_000;
+        assert_eq!(clock.extend((WRAP - 5_000) as u32), WRAP - 5_000);
+        assert_eq!(clock.extend(2_000), WRAP + 2_000);
+        assert_eq!(clock.update(2_000), WRAP + 2_000);
+        assert_eq!(clock.extend((WRAP - 5_000) as u32), WRAP - 5_000);
+        assert_eq!(clock.update(3_000_000), WRAP + 3_000_000);
+    }
+
+    #[test]
+    fn test_tx_count_rejects_ambiguous_targets() {
+        let now = WRAP - 1_000;
+        assert_eq!(tx_count(now, WRAP + 500).unwrap(), 500);
+        assert_matches!(tx_count(now, now), Err(Error::TooLate));
+        assert_matches!(tx_count(now, now + MAX_LEAD), Err(Error::TooEarly));
+        assert_matches!(tx_count(now, now + WRAP), Err(Error::TooEarly));
+    }
+}
@@ -1,4 +1,5 @@
 use quick_error::quick_error;
+use std::fmt;
 
 /// A common result type for this crate.
 pub type Result<T = ()> = ::std::result::Result<T, Error>;
@@ -7,20 +8,80 @@ quick_error! {
     /// A common error type for this crate.
     #[derive(Debug)]
     pub enum Error {
-        /// Device is currently opened in same process.
-        Busy {
+        /// Device is currently opened, by this or another process
+        /// with the given PID, if known.
+        Busy(pid: Option<u32>) {
             description("concentrator device is already in use")
+            display("concentrator device is already in use{}", match pid {
+                Some(pid) => format!(" by process {}", pid),
+                None => String::new(),
+            })
         }
-        /// Catch-all error returned by the low-level `libloragw` c
-        /// code.
-        HAL {
-            description("concentrator HAL returned a generic error")
+        /// A call into the low-level `libloragw` c code failed.
+        HAL(err: HalError) {
+            from()
+            description("concentrator HAL call failed")
+            display("{}", err)
         }
         /// A buffer, primarily transmit payloads, is too large for
         /// the LoRa packet format.
         Size {
             description("provided buffer is too large")
         }
+        /// A transmission was denied because listen-before-talk
+        /// found the channel busy, or the packet is not allowed by
+        /// the LBT configuration.
+        LBT {
+            description("transmission denied by listen-before-talk")
+        }
+        /// The downlink queue has no room for another packet.
+        QueueFull {
+            description("TX queue is full")
+        }
+        /// A packet's transmit time is too soon, or has passed,
+        /// for it to be handed to the concentrator.
+        TooLate {
+            description("too late to transmit packet")
+        }
+        /// A packet's transmit time is too far ahead of the
+        /// concentrator's counter to be told apart from an earlier
+        /// time once truncated to the counter's 32 bits.
+        TooEarly {
+            description("too early to transmit packet")
+        }
+        /// A transmission would exceed the duty cycle of its
+        /// sub-band in the configured region.
+        DutyCycle {
+            description("transmission would exceed regional duty cycle")
+        }
+        /// A transmission would last longer than the configured
+        /// region allows.
+        DwellTime {
+            description("transmission would exceed regional dwell time")
+        }
+        /// A packet's transmission would overlap another's.
+        Collision {
+            description("packet collides with another scheduled transmission")
+        }
+        /// The thread owning the concentrator, see
+        /// `ConcentratorHandle`, has exited.
+        Closed {
+            description("concentrator thread has exited")
+        }
+        /// The requested operation or parameter is not supported by
+        /// the selected concentrator chip, or support for the chip
+        /// was not compiled in.
+        Unsupported {
+            description("not supported by this concentrator")
+        }
+        /// I/O error, e.g. while reading from a GPS receiver's serial
+        /// port.
+        Io(err: ::std::io::Error) {
+            from()
+            description("I/O error")
+            display("I/O error: {}", err)
+            cause(err)
+        }
         /// Represents and error when attempting to convert between
         /// this crate's high-level types and those defined in
         /// `libloragw`.
@@ -30,18 +91,84 @@ quick_error! {
     }
 }
 
+/// Describes a failed call into `libloragw`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HalError {
+    /// Name of the function which failed.
+    pub call: &'static str,
+    /// Broad cause of the failure.
+    pub kind: HalErrorKind,
+    /// The function's return code.
+    pub code: i32,
+    /// The call's relevant arguments, e.g. a chain index or
+    /// frequency. May be empty.
+    pub context: String,
+}
+
+impl fmt::Display for HalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "HAL call {} failed ({}, code {})",
+            self.call, self.kind, self.code
+        )?;
+        if !self.context.is_empty() {
+            write!(f, ": {}", self.context)?;
+        }
+        Ok(())
+    }
+}
+
+/// Broad cause of a failed HAL call, as far as can be told from the
+/// call which failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HalErrorKind {
+    /// The HAL rejected a configuration or parameter, e.g. a
+    /// frequency outside a radio's range.
+    Config,
+    /// Communicating with the concentrator failed, e.g. it isn't
+    /// present or doesn't respond over SPI.
+    Spi,
+    /// The call isn't valid in the concentrator's current state, e.g.
+    /// receiving before it's started.
+    State,
+}
+
+impl fmt::Display for HalErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            HalErrorKind::Config => "configuration error",
+            HalErrorKind::Spi => "SPI error",
+            HalErrorKind::State => "state error",
+        })
+    }
+}
+
 /// Wraps a `libloragw-sys` function call and:
+/// - resolves `$fn` in the `llg` module in scope at the call site
 /// - wraps the return code in a `Result`
-/// - logs name of FFI function on error
-macro_rules! hal_call{
-    ( $fn:ident ( $($arg:expr),* ) ) => {
-        match crate::llg::$fn ( $($arg),* ) {
-            -1 => {
-                log::error!("HAL call {} returned an error", stringify!($fn));
-                Err($crate::error::Error::HAL)
-            }
+/// - on error, returns an `Error::HAL` of `$kind` naming `$fn`, with
+///   an optional `format!`-style context describing the arguments,
+///   and logs it
+macro_rules! hal_call {
+    ( @context ) => { String::new() };
+    ( @context $($context:tt)+ ) => { format!($($context)+) };
+    ( $kind:ident, $fn:ident ( $($arg:expr),* ) $(; $($context:tt)+ )? ) => {
+        match llg::$fn ( $($arg),* ) {
             val if val >= 0 => Ok(val as usize),
-            invalid => panic!("HAL call {} returned invalid value {}", stringify!($fn), invalid),
+            code => {
+                if code != -1 {
+                    log::warn!("HAL call {} returned unexpected value {}", stringify!($fn), code);
+                }
+                let err = $crate::error::HalError {
+                    call: stringify!($fn),
+                    kind: $crate::error::HalErrorKind::$kind,
+                    code,
+                    context: hal_call!(@context $($($context)+)?),
+                };
+                log::error!("{}", err);
+                Err($crate::error::Error::HAL(err))
+            }
         }
     }
 }
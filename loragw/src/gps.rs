@@ -0,0 +1,476 @@
+//! Serial GPS receivers.
+//!
+//! A [`Gps`] reads NMEA (and u-blox UBX) messages from a receiver's
+//! serial port and keeps a reference between the concentrator's
+//! internal counter and UTC, which gives received packets absolute
+//! timestamps.
+//!
+//! Parsing and time conversions are done by the HAL's `loragw_gps`,
+//! which is the same in both HALs and never touches the concentrator,
+//! so a `Gps` works with either chip. Like the rest of the HAL, it
+//! keeps its state in globals, so only one `Gps` may be open at a
+//! time.
+
+use crate::{
+    error::{Error, Result},
+    RxPacketLoRa,
+};
+#[cfg(feature = "sx1301")]
+use libloragw_sx1301_sys as llg;
+#[cfg(not(feature = "sx1301"))]
+use libloragw_sx1302_sys as llg;
+use std::{
+    convert::TryFrom,
+    ffi::CString,
+    io,
+    os::{
+        raw::{c_char, c_int},
+        unix::{ffi::OsStrExt, io::RawFd},
+    },
+    path::Path,
+    process, ptr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{self, Instant, SystemTime, UNIX_EPOCH},
+};
+
+// Ensures we only have 0 or 1 GPS instances opened at a time, as
+// `loragw_gps` parses into globals.
+static GPS_IS_OPEN: AtomicBool = AtomicBool::new(false);
+
+/// A time reference older than this is considered lost.
+const MAX_REF_AGE: time::Duration = time::Duration::from_secs(30);
+
+const UBX_SYNC_CHAR: u8 = 0xB5;
+const NMEA_SYNC_CHAR: u8 = b'$';
+/// Shortest message the HAL will attempt to parse.
+const MIN_MSG_SIZE: usize = 8;
+
+/// A serial GPS receiver.
+pub struct Gps {
+    /// Serial port, opened and configured by `lgw_gps_enable`.
+    fd: RawFd,
+    /// Bytes read from `fd` but not parsed yet.
+    buf: [u8; 256],
+    len: usize,
+    /// Counter to UTC/GPS time reference, maintained by
+    /// `lgw_gps_sync`.
+    tref: llg::tref,
+    /// When `tref` was last successfully updated.
+    synced_at: Option<Instant>,
+    /// Whether the receiver outputs UBX NAV-TIMEGPS messages, which
+    /// carry GPS time.
+    ubx: bool,
+}
+
+impl Gps {
+    /// Opens and configures the GPS receiver on `tty_path`.
+    ///
+    /// `family` names the receiver (e.g. `"ubx7"`), and is only used
+    /// by the HAL to warn about untested receivers.
+    pub fn open(tty_path: &Path, family: Option<&str>) -> Result<Self> {
+        if GPS_IS_OPEN
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            log::error!("GPS busy");
+            return Err(Error::Busy(Some(process::id())));
+        }
+        match enable(tty_path, family) {
+            Ok(fd) => Ok(Gps {
+                fd,
+                buf: [0; 256],
+                len: 0,
+                tref: llg::tref::default(),
+                synced_at: None,
+                ubx: false,
+            }),
+            Err(e) => {
+                GPS_IS_OPEN.store(false, Ordering::Release);
+                Err(e)
+            }
+        }
+    }
+
+    /// Parses everything the receiver has sent since the last call,
+    /// without blocking.
+    ///
+    /// `counter` is called for every time solution to get the
+    /// concentrator counter latched at the PPS edge the solution
+    /// refers to, i.e. `Concentrator::pps_counter`. This should be called
+    /// often enough for that to happen before the next edge.
+    pub fn poll<F>(&mut self, mut counter: F) -> Result
+    where
+        F: FnMut() -> Result<time::Duration>,
+    {
+        loop {
+            let free = &mut self.buf[self.len..];
+            let n = unsafe { libc::read(self.fd, free.as_mut_ptr() as *mut _, free.len()) };
+            if n < 0 {
+                let err = io::Error::last_os_error();
+                match err.kind() {
+                    io::ErrorKind::WouldBlock => return Ok(()),
+                    io::ErrorKind::Interrupted => continue,
+                    _ => return Err(err.into()),
+                }
+            }
+            if n == 0 {
+                return Ok(());
+            }
+            self.len += n as usize;
+            self.parse(&mut counter)?;
+        }
+    }
+
+    /// Forgets the time reference, which no longer holds once the
+    /// concentrator restarts and its counter with it.
+    ///
+    /// `utc` and `gps_time` return `None` until the receiver's next
+    /// time solution is accepted.
+    pub fn reset_time_ref(&mut self) {
+        self.tref = llg::tref::default();
+        self.synced_at = None;
+    }
+
+    /// Converts a concentrator `timestamp` to UTC.
+    ///
+    /// Returns `None` until the receiver has a fix and a few
+    /// consistent time solutions have been received, or if it lost
+    /// its fix more than 30 seconds ago.
+    pub fn utc(&self, timestamp: time::Duration) -> Option<SystemTime> {
+        let tref = self.time_ref()?;
+        let mut utc = llg::timespec::default();
+        if unsafe { llg::lgw_cnt2utc(*tref, timestamp.as_micros() as u32, &mut utc) } != 0 {
+            return None;
+        }
+        Some(UNIX_EPOCH + from_timespec(utc)?)
+    }
+
+    /// Converts a concentrator `timestamp` to time since the GPS
+    /// epoch (1980-01-06T00:00:00Z).
+    ///
+    /// In addition to `utc`'s requirements, the receiver must output
+    /// u-blox UBX NAV-TIMEGPS messages, as NMEA sentences only carry
+    /// UTC.
+    pub fn gps_time(&self, timestamp: time::Duration) -> Option<time::Duration> {
+        if !self.ubx {
+            return None;
+        }
+        let tref = self.time_ref()?;
+        let mut gps_time = llg::timespec::default();
+        if unsafe { llg::lgw_cnt2gps(*tref, timestamp.as_micros() as u32, &mut gps_time) } != 0 {
+            return None;
+        }
+        from_timespec(gps_time)
+    }
+
+    /// Sets `packet`'s UTC and GPS timestamps from its counter
+    /// timestamp.
+    pub fn stamp(&self, packet: &mut RxPacketLoRa) {
+        packet.utc = self.utc(packet.timestamp);
+        packet.gps_time = self.gps_time(packet.timestamp);
+    }
+}
+
+// Private functions.
+impl Gps {
+    /// Parses all complete messages in `buf`, keeping any trailing
+    /// partial message for the next call.
+    fn parse<F>(&mut self, counter: &mut F) -> Result
+    where
+        F: FnMut() -> Result<time::Duration>,
+    {
+        let mut start = 0;
+        while self.len - start >= MIN_MSG_SIZE {
+            let frame = &self.buf[start..self.len];
+            match frame[0] {
+                UBX_SYNC_CHAR => {
+                    let mut size = 0;
+                    let msg = unsafe {
+                        llg::lgw_parse_ubx(
+                            frame.as_ptr() as *const c_char,
+                            frame.len() as llg::size_t,
+                            &mut size,
+                        )
+                    };
+                    let size = size as usize;
+                    if msg == llg::gps_msg_INCOMPLETE && size <= self.buf.len() {
+                        break;
+                    }
+                    if msg == llg::gps_msg_UBX_NAV_TIMEGPS {
+                        if !self.ubx {
+                            log::info!("GPS receiver reports GPS time");
+                            self.ubx = true;
+                        }
+                        self.sync(counter)?;
+                    }
+                    // A sync char which doesn't start a message is
+                    // skipped on its own.
+                    start += if msg == llg::gps_msg_INCOMPLETE || size == 0 {
+                        1
+                    } else {
+                        size
+                    };
+                }
+                NMEA_SYNC_CHAR => {
+                    let size = match frame.iter().position(|&b| b == b'\n') {
+                        Some(idx) => idx + 1,
+                        None if frame.len() < self.buf.len() => break,
+                        None => frame.len(),
+                    };
+                    let msg = unsafe {
+                        llg::lgw_parse_nmea(frame.as_ptr() as *const c_char, size as c_int)
+                    };
+                    // UBX NAV-TIMEGPS is preferred when available, as
+                    // it also carries GPS time.
+                    if msg == llg::gps_msg_NMEA_RMC && !self.ubx {
+                        self.sync(counter)?;
+                    }
+                    start += size;
+                }
+                _ => start += 1,
+            }
+        }
+        self.buf.copy_within(start..self.len, 0);
+        self.len -= start;
+        Ok(())
+    }
+
+    /// Updates the time reference with the most recently parsed time
+    /// solution.
+    fn sync<F>(&mut self, counter: &mut F) -> Result
+    where
+        F: FnMut() -> Result<time::Duration>,
+    {
+        let mut utc = llg::timespec::default();
+        let mut gps_time = llg::timespec::default();
+        if unsafe { llg::lgw_gps_get(&mut utc, &mut gps_time, ptr::null_mut(), ptr::null_mut()) }
+            != 0
+        {
+            log::debug!("GPS has no valid time yet");
+            return Ok(());
+        }
+        let count_us = counter()?.as_micros() as u32;
+        // `lgw_gps_sync` rejects solutions which don't match the
+        // previous reference within 10 ppm, and only resets the
+        // reference after three such rejections in a row.
+        if unsafe { llg::lgw_gps_sync(&mut self.tref, count_us, utc, gps_time) } == 0 {
+            log::trace!(
+                "GPS sync: counter {} is {}.{:09} UTC",
+                count_us,
+                utc.tv_sec,
+                utc.tv_nsec
+            );
+            self.synced_at = Some(Instant::now());
+        } else {
+            log::debug!("GPS sync: rejected counter {}", count_us);
+        }
+        Ok(())
+    }
+
+    fn time_ref(&self) -> Option<&llg::tref> {
+        match self.synced_at {
+            Some(at) if at.elapsed() < MAX_REF_AGE => Some(&self.tref),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for Gps {
+    fn drop(&mut self) {
+        log::info!("closing GPS");
+        // Restores the serial port's settings and closes it. The HAL
+        // reports an error when `close` succeeds, so the result is
+        // meaningless.
+        unsafe { llg::lgw_gps_disable(self.fd) };
+        GPS_IS_OPEN.store(false, Ordering::Release);
+    }
+}
+
+/// Opens and configures the serial port, returning its file
+/// descriptor in non-blocking mode.
+fn enable(tty_path: &Path, family: Option<&str>) -> Result<RawFd> {
+    let tty_path = CString::new(tty_path.as_os_str().as_bytes()).map_err(|_| {
+        log::error!("GPS path {:?} contains a NUL byte", tty_path);
+        Error::Data
+    })?;
+    let family = match family.map(CString::new) {
+        Some(Ok(family)) => Some(family),
+        Some(Err(_)) => {
+            log::error!("GPS family {:?} contains a NUL byte", family);
+            return Err(Error::Data);
+        }
+        None => None,
+    };
+    let mut fd = -1;
+    unsafe {
+        hal_call!(Config, lgw_gps_enable(
+            tty_path.as_ptr() as *mut c_char,
+            family
+                .as_ref()
+                .map_or(ptr::null_mut(), |family| family.as_ptr() as *mut c_char),
+            0,
+            &mut fd
+        ); "{:?}", tty_path)
+    }?;
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+        let err = io::Error::last_os_error();
+        log::error!("failed to make GPS serial port non-blocking: {}", err);
+        unsafe { llg::lgw_gps_disable(fd) };
+        return Err(err.into());
+    }
+    Ok(fd)
+}
+
+fn from_timespec(ts: llg::timespec) -> Option<time::Duration> {
+    Some(time::Duration::new(
+        u64::try_from(ts.tv_sec).ok()?,
+        u32::try_from(ts.tv_nsec).ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lazy_static::lazy_static;
+    use std::{fs, io::Write, os::unix::io::FromRawFd, path::PathBuf, sync::Mutex, thread};
+
+    lazy_static! {
+        static ref TEST_MUTEX: Mutex<()> = Mutex::new(());
+    }
+
+    /// Recorded at 2019-08-01T12:00:00Z, one second per pair.
+    const NMEA: [&str; 10] = [
+        "$GPGGA,120000.00,3746.49390,N,12225.17430,W,1,09,0.92,16.4,M,-29.9,M,,*55\r\n",
+        "$GPRMC,120000.00,A,3746.49390,N,12225.17430,W,0.021,,010819,,,A*65\r\n",
+        "$GPGGA,120001.00,3746.49390,N,12225.17430,W,1,09,0.92,16.4,M,-29.9,M,,*54\r\n",
+        "$GPRMC,120001.00,A,3746.49390,N,12225.17430,W,0.021,,010819,,,A*64\r\n",
+        "$GPGGA,120002.00,3746.49390,N,12225.17430,W,1,09,0.92,16.4,M,-29.9,M,,*57\r\n",
+        "$GPRMC,120002.00,A,3746.49390,N,12225.17430,W,0.021,,010819,,,A*67\r\n",
+        "$GPGGA,120003.00,3746.49390,N,12225.17430,W,1,09,0.92,16.4,M,-29.9,M,,*56\r\n",
+        "$GPRMC,120003.00,A,3746.49390,N,12225.17430,W,0.021,,010819,,,A*66\r\n",
+        "$GPGGA,120004.00,3746.49390,N,12225.17430,W,1,09,0.92,16.4,M,-29.9,M,,*51\r\n",
+        "$GPRMC,120004.00,A,3746.49390,N,12225.17430,W,0.021,,010819,,,A*61\r\n",
+    ];
+    /// 2019-08-01T12:00:00Z
+    const UTC_SECS: u64 = 1_564_660_800;
+    /// 2019-08-01T12:00:00Z is 2019-08-01T12:00:18 GPS time.
+    const GPS_WEEK: u16 = 2064;
+    const GPS_TOW_MS: u32 = 388_818_000;
+
+    /// Opens a pseudo-terminal, returning its master end and the path
+    /// of its slave end.
+    fn pty() -> (fs::File, PathBuf) {
+        let (mut master, mut slave) = (0, 0);
+        let res = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                ptr::null_mut(),
+                ptr::null(),
+                ptr::null(),
+            )
+        };
+        assert_eq!(res, 0, "{}", io::Error::last_os_error());
+        let path = fs::read_link(format!("/proc/self/fd/{}", slave)).unwrap();
+        unsafe { libc::close(slave) };
+        (unsafe { fs::File::from_raw_fd(master) }, path)
+    }
+
+    /// Builds a valid UBX NAV-TIMEGPS message.
+    fn nav_timegps(week: u16, tow_ms: u32) -> Vec<u8> {
+        let mut msg = vec![0xB5, 0x62, 0x01, 0x20, 16, 0];
+        msg.extend_from_slice(&tow_ms.to_le_bytes());
+        msg.extend_from_slice(&0i32.to_le_bytes());
+        msg.extend_from_slice(&week.to_le_bytes());
+        // leap seconds, validity flags, and time accuracy
+        msg.extend_from_slice(&[18, 0x07, 0, 0, 0, 0]);
+        let (mut ck_a, mut ck_b) = (0u8, 0u8);
+        for b in &msg[2..] {
+            ck_a = ck_a.wrapping_add(*b);
+            ck_b = ck_b.wrapping_add(ck_a);
+        }
+        msg.push(ck_a);
+        msg.push(ck_b);
+        msg
+    }
+
+    /// Writes `data` to the receiver side of `gps` and polls until a
+    /// time solution latched at `count_us` was consumed.
+    fn feed(gps: &mut Gps, master: &mut fs::File, data: &[u8], count_us: u64) {
+        master.write_all(data).unwrap();
+        for _ in 0..1000 {
+            let mut synced = false;
+            gps.poll(|| {
+                synced = true;
+                Ok(time::Duration::from_micros(count_us))
+            })
+            .unwrap();
+            if synced {
+                return;
+            }
+            thread::sleep(time::Duration::from_millis(1));
+        }
+        panic!("no time solution in {:?}", String::from_utf8_lossy(data));
+    }
+
+    #[test]
+    fn test_sync_from_nmea() {
+        let _lock = TEST_MUTEX.lock().unwrap();
+        let (mut master, path) = pty();
+        let mut gps = Gps::open(&path, None).unwrap();
+        assert_eq!(gps.utc(time::Duration::from_secs(1)), None);
+        for (sec, sentences) in NMEA.chunks(2).enumerate() {
+            let data = sentences.concat();
+            feed(
+                &mut gps,
+                &mut master,
+                data.as_bytes(),
+                1_000_000 * sec as u64,
+            );
+        }
+        assert_eq!(
+            gps.utc(time::Duration::from_micros(4_500_000)),
+            Some(UNIX_EPOCH + time::Duration::from_millis(UTC_SECS * 1000 + 4_500))
+        );
+        // NMEA only carries UTC.
+        assert_eq!(gps.gps_time(time::Duration::from_micros(4_500_000)), None);
+    }
+
+    #[test]
+    fn test_sync_from_ubx() {
+        let _lock = TEST_MUTEX.lock().unwrap();
+        let (mut master, path) = pty();
+        let mut gps = Gps::open(&path, Some("ubx7")).unwrap();
+        // The HAL's configuration message for the receiver.
+        let mut cfg = [0; 16];
+        let mut read = 0;
+        while read < cfg.len() {
+            read += io::Read::read(&mut master, &mut cfg[read..]).unwrap();
+        }
+        assert_eq!(&cfg[..4], &[0xB5, 0x62, 0x06, 0x01]);
+        for (sec, sentences) in NMEA.chunks(2).enumerate() {
+            let mut data = sentences.concat().into_bytes();
+            data.extend(nav_timegps(GPS_WEEK, GPS_TOW_MS + 1000 * sec as u32));
+            feed(&mut gps, &mut master, &data, 1_000_000 * sec as u64);
+        }
+        assert_eq!(
+            gps.utc(time::Duration::from_micros(4_250_000)),
+            Some(UNIX_EPOCH + time::Duration::from_millis(UTC_SECS * 1000 + 4_250))
+        );
+        assert_eq!(
+            gps.gps_time(time::Duration::from_micros(4_250_000)),
+            Some(time::Duration::from_millis(
+                u64::from(GPS_WEEK) * 604_800_000 + u64::from(GPS_TOW_MS) + 4_250
+            ))
+        );
+    }
+
+    #[test]
+    fn test_double_open_fails() {
+        let _lock = TEST_MUTEX.lock().unwrap();
+        let (_master, path) = pty();
+        let _gps = Gps::open(&path, None).unwrap();
+        assert_matches!(Gps::open(&path, None), Err(Error::Busy(_)));
+    }
+}
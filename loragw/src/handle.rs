@@ -0,0 +1,367 @@
+use crate::{ConcentratorBackend, Error, Radio, Result, RxPacket, TxPacket, TxStatus, VersionInfo};
+use std::{
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread, time,
+};
+
+/// A call to run on the HAL thread.
+type Call = Box<dyn FnOnce(&dyn ConcentratorBackend) + Send>;
+
+/// Called with the outcome of a transmission once it has gone out.
+type Sent = Box<dyn FnOnce(Result) + Send>;
+
+enum Command {
+    Call(Call),
+    Subscribe(Subscriber),
+    Transmit(TxPacket, Sent),
+}
+
+enum Subscriber {
+    Sync(mpsc::Sender<RxPacket>),
+    #[cfg(feature = "async")]
+    Async(futures_channel::mpsc::UnboundedSender<Result<RxPacket>>),
+}
+
+impl Subscriber {
+    /// Returns `false` if the subscriber has hung up.
+    fn send(&self, pkt: &RxPacket) -> bool {
+        match self {
+            Subscriber::Sync(tx) => tx.send(pkt.clone()).is_ok(),
+            #[cfg(feature = "async")]
+            Subscriber::Async(tx) => tx.unbounded_send(Ok(pkt.clone())).is_ok(),
+        }
+    }
+
+    /// Tells the subscriber receiving failed with `err`.
+    #[cfg_attr(not(feature = "async"), allow(unused_variables))]
+    fn fail(&self, err: &Error) {
+        match self {
+            Subscriber::Sync(_) => (),
+            #[cfg(feature = "async")]
+            Subscriber::Async(tx) => {
+                // Only HAL errors can be copied. The thread is
+                // exiting regardless, so report others as such.
+                let err = match err {
+                    Error::HAL(err) => Error::HAL(err.clone()),
+                    _ => Error::Closed,
+                };
+                let _ = tx.unbounded_send(Err(err));
+            }
+        }
+    }
+}
+
+struct Inner {
+    /// Only `None` while dropping.
+    commands: Mutex<Option<mpsc::Sender<Command>>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // Disconnecting the command channel ends the thread, which
+        // drops, and so stops, the concentrator.
+        if let Ok(mut commands) = self.commands.lock() {
+            commands.take();
+        }
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("concentrator thread panicked");
+            }
+        }
+    }
+}
+
+/// A thread-safe handle to a started concentrator.
+///
+/// `Concentrator` is not `Sync`, as `libloragw` keeps its state in
+/// globals. A `ConcentratorHandle` moves a started concentrator to a
+/// private thread, which polls it for received packets and runs calls
+/// made through any clone of the handle, one at a time.
+///
+/// Received packets are delivered to every channel returned by
+/// `subscribe`. The concentrator is dropped, stopping it, once the
+/// last clone of the handle is.
+#[derive(Clone)]
+pub struct ConcentratorHandle {
+    inner: Arc<Inner>,
+}
+
+impl ConcentratorHandle {
+    /// Moves `concentrator` to a new thread which polls it for
+    /// received packets every `interval`.
+    pub fn spawn<B>(concentrator: B, interval: time::Duration) -> Result<Self>
+    where
+        B: ConcentratorBackend + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("loragw".into())
+            .spawn(move || run(&concentrator, &rx, interval))?;
+        Ok(ConcentratorHandle {
+            inner: Arc::new(Inner {
+                commands: Mutex::new(Some(tx)),
+                thread: Some(thread),
+            }),
+        })
+    }
+
+    /// Returns a channel of packets received from now on.
+    ///
+    /// The channel is closed if the concentrator thread exits, e.g.
+    /// on a receive error.
+    pub fn subscribe(&self) -> Result<mpsc::Receiver<RxPacket>> {
+        let (tx, rx) = mpsc::channel();
+        self.send(Command::Subscribe(Subscriber::Sync(tx)))?;
+        Ok(rx)
+    }
+
+    /// Transmit `packet` over the air, returning once it has been sent
+    /// rather than just handed to the concentrator.
+    ///
+    /// Completion is checked each time the concentrator thread polls
+    /// for received packets.
+    pub fn transmit_wait(&self, packet: TxPacket) -> Result {
+        let (tx, rx) = mpsc::channel();
+        self.send(Command::Transmit(
+            packet,
+            Box::new(move |res| {
+                // The caller only goes away if it panicked.
+                let _ = tx.send(res);
+            }),
+        ))?;
+        rx.recv().map_err(|_| Error::Closed)?
+    }
+
+    /// Returns a stream of packets received from now on.
+    ///
+    /// If the concentrator thread exits on a receive error, the
+    /// stream yields the error and ends.
+    #[cfg(feature = "async")]
+    pub fn packets(&self) -> Result<impl futures_core::Stream<Item = Result<RxPacket>> + Unpin> {
+        let (tx, rx) = futures_channel::mpsc::unbounded();
+        self.send(Command::Subscribe(Subscriber::Async(tx)))?;
+        Ok(rx)
+    }
+
+    /// Transmit `packet` over the air, resolving once it has been
+    /// sent, like `transmit_wait`.
+    #[cfg(feature = "async")]
+    pub async fn transmit_async(&self, packet: TxPacket) -> Result {
+        let (tx, rx) = futures_channel::oneshot::channel();
+        self.send(Command::Transmit(
+            packet,
+            Box::new(move |res| {
+                // The caller only goes away if the future was dropped.
+                let _ = tx.send(res);
+            }),
+        ))?;
+        rx.await.map_err(|_| Error::Closed)?
+    }
+
+    /// Transmit `packet` over the air.
+    ///
+    /// Like `Concentrator::transmit`, this waits for the transmitter
+    /// to be free, and receiving is paused meanwhile.
+    pub fn transmit(&self, packet: TxPacket) -> Result {
+        self.call(move |c| c.transmit(packet))
+    }
+
+    /// Returns the current transmit status of `radio`.
+    pub fn transmit_status(&self, radio: Radio) -> Result<TxStatus> {
+        self.call(move |c| c.transmit_status(radio))
+    }
+
+    /// Returns the current value of the concentrator's internal 1 MHz
+    /// counter.
+    pub fn counter(&self) -> Result<time::Duration> {
+        self.call(|c| c.counter())
+    }
+
+    /// Returns the value the internal counter had at the last PPS edge
+    /// from the GPS receiver.
+    pub fn pps_counter(&self) -> Result<time::Duration> {
+        self.call(|c| c.pps_counter())
+    }
+
+    /// Abort any scheduled or in-flight transmission.
+    pub fn abort_tx(&self) -> Result {
+        self.call(|c| c.abort_tx())
+    }
+
+    /// Abort a scheduled or in-flight transmission on `radio` only.
+    pub fn abort_tx_on(&self, radio: Radio) -> Result {
+        self.call(move |c| c.abort_tx_on(radio))
+    }
+
+    /// Returns the board temperature, in degrees Celsius.
+    pub fn temperature(&self) -> Result<f32> {
+        self.call(|c| c.temperature())
+    }
+
+    /// Returns the HAL version, and the chip and FPGA versions if
+    /// available.
+    pub fn version_info(&self) -> Result<VersionInfo> {
+        self.call(|c| c.version_info())
+    }
+
+    /// Runs `f` on the concentrator thread, and returns its result.
+    fn call<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn ConcentratorBackend) -> Result<T> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        self.send(Command::Call(Box::new(move |c| {
+            // The caller only goes away if it panicked.
+            let _ = tx.send(f(c));
+        })))?;
+        rx.recv().map_err(|_| Error::Closed)?
+    }
+
+    fn send(&self, command: Command) -> Result {
+        let commands = self.inner.commands.lock().map_err(|_| Error::Closed)?;
+        match commands.as_ref() {
+            Some(commands) => commands.send(command).map_err(|_| Error::Closed),
+            None => Err(Error::Closed),
+        }
+    }
+}
+
+/// Body of the concentrator thread.
+fn run(
+    concentrator: &dyn ConcentratorBackend,
+    commands: &mpsc::Receiver<Command>,
+    interval: time::Duration,
+) {
+    let mut subscribers: Vec<Subscriber> = Vec::new();
+    // The transmission waiting to go out, if any, and its radio. The
+    // concentrator holds one packet at a time.
+    let mut sending: Option<(Radio, Sent)> = None;
+    let mut next_poll = time::Instant::now();
+    loop {
+        let timeout = next_poll.saturating_duration_since(time::Instant::now());
+        match commands.recv_timeout(timeout) {
+            Ok(Command::Call(f)) => f(concentrator),
+            Ok(Command::Subscribe(subscriber)) => subscribers.push(subscriber),
+            Ok(Command::Transmit(packet, sent)) => {
+                if let Some((radio, previous)) = sending.take() {
+                    previous(wait_sent(concentrator, radio));
+                }
+                let radio = packet.radio();
+                match concentrator.transmit(packet) {
+                    Ok(()) => sending = Some((radio, sent)),
+                    Err(e) => sent(Err(e)),
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        let now = time::Instant::now();
+        if now < next_poll {
+            continue;
+        }
+        next_poll = now + interval;
+        if let Some((radio, sent)) = sending.take() {
+            match concentrator.transmit_status(radio) {
+                Ok(TxStatus::Free) => sent(Ok(())),
+                Ok(_) => sending = Some((radio, sent)),
+                Err(e) => sent(Err(e)),
+            }
+        }
+        loop {
+            match concentrator.receive() {
+                Ok(Some(packets)) => {
+                    for pkt in packets {
+                        // Forget subscribers which hung up.
+                        subscribers.retain(|subscriber| subscriber.send(&pkt));
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    log::error!("concentrator thread exiting on receive error: {}", e);
+                    for subscriber in &subscribers {
+                        subscriber.fail(&e);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+    log::debug!("concentrator thread exiting, all handles dropped");
+}
+
+/// Waits for the transmitter of `radio` to be free.
+fn wait_sent(concentrator: &dyn ConcentratorBackend, radio: Radio) -> Result {
+    while concentrator.transmit_status(radio)? != TxStatus::Free {
+        thread::sleep(time::Duration::from_millis(5));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sim::test_util::configured, SimTraffic};
+
+    fn spawn_sim() -> ConcentratorHandle {
+        let sim = configured(SimTraffic {
+            interval: Some(time::Duration::from_millis(1)),
+            ..Default::default()
+        });
+        sim.start().unwrap();
+        ConcentratorHandle::spawn(sim, time::Duration::from_millis(1)).unwrap()
+    }
+
+    #[test]
+    fn test_handle_shared_across_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ConcentratorHandle>();
+
+        let handle = spawn_sim();
+        let first = handle.subscribe().unwrap();
+        let second = handle.subscribe().unwrap();
+
+        let other = handle.clone();
+        let counter = thread::spawn(move || other.counter().unwrap())
+            .join()
+            .unwrap();
+        assert!(handle.counter().unwrap() >= counter);
+        assert_eq!(handle.transmit_status(Radio::R0).unwrap(), TxStatus::Free);
+
+        // Each subscriber gets its own copy of received packets.
+        let timeout = time::Duration::from_secs(1);
+        for rx in &[&first, &second] {
+            for _ in 0..3 {
+                rx.recv_timeout(timeout).unwrap();
+            }
+        }
+
+        // Dropping the last handle ends the thread and closes the
+        // channels.
+        drop(handle);
+        first.iter().for_each(drop);
+        assert_eq!(
+            first.try_recv().unwrap_err(),
+            mpsc::TryRecvError::Disconnected
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_async_transmit_resolves_once_sent() {
+        let handle = spawn_sim();
+        let mut packets = futures_executor::block_on_stream(handle.packets().unwrap());
+        packets.next().unwrap().unwrap();
+
+        let packet = crate::sim::test_util::lora_tx(vec![0; 10]);
+        let toa = packet.time_on_air();
+        let started = time::Instant::now();
+        futures_executor::block_on(handle.transmit_async(packet)).unwrap();
+        assert!(started.elapsed() >= toa);
+        assert_eq!(handle.transmit_status(Radio::R0).unwrap(), TxStatus::Free);
+    }
+}
@@ -0,0 +1,424 @@
+//! A just-in-time downlink queue.
+//!
+//! The concentrators have a single TX buffer (per RF chain on the
+//! SX1302), so only one packet can be handed to the HAL at a time.
+//! `JitQueue` holds timestamped packets in transmit order and hands
+//! each one to the HAL shortly before its slot, once the transmitter
+//! is free, without blocking the caller in the meantime.
+//!
+//! All times are values of the concentrator's microsecond counter,
+//! extended to 64 bits, so packets may be queued for any time ahead
+//! of it.
+
+use crate::{error::Error, Chip, ConcentratorBackend, Radio, Result, TxMode, TxPacket, TxStatus};
+use std::{collections::VecDeque, time::Duration};
+
+/// How long before its start a packet is handed to the HAL.
+pub const HANDOFF: Duration = Duration::from_millis(30);
+
+/// Shortest time before its start a packet may be enqueued or handed
+/// to the HAL, which needs 1.5 ms to start transmitting.
+pub const MIN_LEAD: Duration = Duration::from_millis(2);
+
+/// Minimum idle time between the end of one packet and the start of
+/// the next.
+///
+/// The next packet can only be handed off once the previous one has
+/// gone out, so this must cover noticing that, plus `MIN_LEAD`.
+pub const GUARD: Duration = Duration::from_millis(10);
+
+struct Entry<T> {
+    start: u64,
+    end: u64,
+    /// Index of the TX path the packet goes out on.
+    path: usize,
+    packet: TxPacket,
+    tag: T,
+}
+
+/// A queue of packets waiting to be transmitted, ordered by start
+/// time.
+///
+/// Each packet carries a caller-defined `tag`, e.g. a request id,
+/// which is returned when the packet is handed to the HAL. Packets
+/// only collide with others on the same TX path, which on the SX1301
+/// is shared by both radios.
+pub struct JitQueue<T> {
+    capacity: usize,
+    shared_tx_path: bool,
+    entries: VecDeque<Entry<T>>,
+    /// End of the most recently handed off packet, per TX path.
+    busy_until: [Option<u64>; 2],
+}
+
+/// Returns `a - b`.
+fn diff(a: u64, b: u64) -> i64 {
+    a as i64 - b as i64
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros() as u64
+}
+
+impl<T> JitQueue<T> {
+    /// Returns an empty queue holding up to `capacity` packets for a
+    /// `chip` concentrator.
+    pub fn new(capacity: usize, chip: Chip) -> Self {
+        JitQueue {
+            capacity,
+            shared_tx_path: chip.shared_tx_path(),
+            entries: VecDeque::with_capacity(capacity),
+            busy_until: [None; 2],
+        }
+    }
+
+    /// Returns the number of queued packets.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no packets are queued.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Queue `packet` for transmission, where `now` is the current
+    /// value of the concentrator's counter.
+    ///
+    /// `TxMode::Timestamp` packets are sent at their timestamp.
+    /// `TxMode::Immediate` packets are given the first free slot at
+    /// least `HANDOFF` from now. `TxMode::PPS` is not supported.
+    ///
+    /// Fails with `Error::QueueFull`, `Error::TooLate` if the packet
+    /// starts less than `MIN_LEAD` from now, or `Error::Collision` if
+    /// it overlaps another packet.
+    pub fn enqueue(&mut self, now: Duration, mut packet: TxPacket, tag: T) -> Result {
+        if self.entries.len() >= self.capacity {
+            log::warn!("JIT queue is full ({} packets)", self.entries.len());
+            return Err(Error::QueueFull);
+        }
+        let now = micros(now);
+        let toa = micros(packet.time_on_air());
+        let path = self.path(packet.radio());
+        let start = match *packet.mode_mut() {
+            TxMode::Immediate => self.first_gap(path, now + micros(HANDOFF), toa),
+            TxMode::Timestamp(ts) => micros(ts),
+            TxMode::PPS(_) => {
+                log::error!("JIT queue does not support PPS transmissions");
+                return Err(Error::Unsupported);
+            }
+        };
+        if diff(start, now) < micros(MIN_LEAD) as i64 {
+            log::warn!("TX at {} is too late, counter is at {}", start, now);
+            return Err(Error::TooLate);
+        }
+        let end = start + toa;
+        if self.collides(path, start, end) {
+            log::warn!("TX at {}..{} collides with a queued packet", start, end);
+            return Err(Error::Collision);
+        }
+        *packet.mode_mut() = TxMode::Timestamp(Duration::from_micros(start));
+        let idx = self
+            .entries
+            .iter()
+            .position(|e| e.start > start)
+            .unwrap_or(self.entries.len());
+        log::debug!("queued TX at {}..{} in slot {}", start, end, idx);
+        self.entries.insert(
+            idx,
+            Entry {
+                start,
+                end,
+                path,
+                packet,
+                tag,
+            },
+        );
+        Ok(())
+    }
+
+    /// Hands the next packet on a TX path to `concentrator` if its
+    /// slot is within `HANDOFF` and the transmitter is free.
+    ///
+    /// Returns the packet's tag along with the result of handing it
+    /// off, or `None` if there was nothing to do. Packets which could
+    /// not be handed off in time are returned with `Error::TooLate`.
+    /// Call this at least every `HANDOFF - MIN_LEAD`, or as indicated
+    /// by `next_due`.
+    pub fn poll(&mut self, concentrator: &dyn ConcentratorBackend) -> Result<Option<(T, Result)>> {
+        if self.entries.is_empty() {
+            return Ok(None);
+        }
+        let now = micros(concentrator.counter()?);
+        // Paths whose next packet is waiting for the transmitter.
+        let mut waiting = [false; 2];
+        for idx in 0..self.entries.len() {
+            let (start, path, radio) = {
+                let entry = &self.entries[idx];
+                (entry.start, entry.path, entry.packet.radio())
+            };
+            let lead = diff(start, now);
+            if lead > micros(HANDOFF) as i64 {
+                break;
+            }
+            if lead < micros(MIN_LEAD) as i64 {
+                let entry = self.entries.remove(idx).unwrap();
+                log::warn!("TX at {} expired in queue, counter is at {}", start, now);
+                return Ok(Some((entry.tag, Err(Error::TooLate))));
+            }
+            if waiting[path] {
+                continue;
+            }
+            if concentrator.transmit_status(radio)? != TxStatus::Free {
+                log::trace!("{:?} transmitter is busy, TX at {} waiting", radio, start);
+                waiting[path] = true;
+                continue;
+            }
+            let entry = self.entries.remove(idx).unwrap();
+            self.busy_until[path] = Some(entry.end);
+            log::debug!("handing off TX at {}, counter is at {}", start, now);
+            return Ok(Some((entry.tag, concentrator.transmit(entry.packet))));
+        }
+        Ok(None)
+    }
+
+    /// Returns how long after `now` `poll` next has work to do, or
+    /// `None` if the queue is empty.
+    pub fn next_due(&self, now: Duration) -> Option<Duration> {
+        self.entries.front().map(|entry| {
+            let due = diff(entry.start, micros(now)) - micros(HANDOFF) as i64;
+            Duration::from_micros(due.max(0) as u64)
+        })
+    }
+
+    /// Removes the first queued packet whose tag matches `pred`, if
+    /// any.
+    pub fn remove<F>(&mut self, mut pred: F) -> Option<(T, TxPacket)>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let idx = self.entries.iter().position(|e| pred(&e.tag))?;
+        self.entries.remove(idx).map(|e| (e.tag, e.packet))
+    }
+
+    /// Returns the index of the TX path `radio` transmits on, indexing
+    /// `busy_until`.
+    fn path(&self, radio: Radio) -> usize {
+        if self.shared_tx_path {
+            0
+        } else {
+            radio as usize
+        }
+    }
+
+    /// Returns `true` if a packet spanning `start..end` on TX path
+    /// `path` would overlap the packet on air or a queued one,
+    /// including guard time.
+    fn collides(&self, path: usize, start: u64, end: u64) -> bool {
+        let guard = micros(GUARD);
+        if let Some(busy_until) = self.busy_until[path] {
+            if start < busy_until + guard {
+                return true;
+            }
+        }
+        self.entries
+            .iter()
+            .filter(|e| e.path == path)
+            .any(|e| start < e.end + guard && e.start < end + guard)
+    }
+
+    /// Returns the first start time at or after `earliest` at which a
+    /// packet lasting `toa` fits on TX path `path`.
+    fn first_gap(&self, path: usize, earliest: u64, toa: u64) -> u64 {
+        let guard = micros(GUARD);
+        let mut start = earliest;
+        if let Some(busy_until) = self.busy_until[path] {
+            start = start.max(busy_until + guard);
+        }
+        for e in self.entries.iter().filter(|e| e.path == path) {
+            if start < e.end + guard && e.start < start + toa + guard {
+                start = e.end + guard;
+            }
+        }
+        start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sim::test_util::{configured, lora},
+        Radio, SimTraffic, TxPacketLoRa,
+    };
+    use std::thread;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// Returns a ~41 ms long packet.
+    fn packet(mode: TxMode) -> TxPacket {
+        TxPacket::LoRa(TxPacketLoRa {
+            mode,
+            ..lora(vec![0; 10])
+        })
+    }
+
+    fn at(ts: Duration) -> TxPacket {
+        packet(TxMode::Timestamp(ts))
+    }
+
+    fn starts<T>(jit: &JitQueue<T>) -> Vec<u64> {
+        jit.entries.iter().map(|e| e.start).collect()
+    }
+
+    #[test]
+    fn test_enqueue_orders_by_start() {
+        let mut jit = JitQueue::new(8, Chip::SX1301);
+        jit.enqueue(ms(0), at(ms(300)), 1).unwrap();
+        jit.enqueue(ms(0), at(ms(100)), 2).unwrap();
+        jit.enqueue(ms(0), at(ms(200)), 3).unwrap();
+        assert_eq!(starts(&jit), vec![100_000, 200_000, 300_000]);
+        assert_eq!(jit.remove(|tag| *tag == 2).map(|(tag, _)| tag), Some(2));
+        assert_eq!(starts(&jit), vec![200_000, 300_000]);
+        assert!(jit.remove(|tag| *tag == 2).is_none());
+    }
+
+    #[test]
+    fn test_enqueue_rejections() {
+        let mut jit = JitQueue::new(2, Chip::SX1301);
+        let now = ms(1_000);
+        assert_matches!(jit.enqueue(now, at(ms(1_001)), ()), Err(Error::TooLate));
+        assert_matches!(jit.enqueue(now, at(ms(900)), ()), Err(Error::TooLate));
+        jit.enqueue(now, at(ms(1_100)), ()).unwrap();
+        assert_matches!(jit.enqueue(now, at(ms(1_120)), ()), Err(Error::Collision));
+        // Overlaps only the guard time after the first packet.
+        assert_matches!(jit.enqueue(now, at(ms(1_143)), ()), Err(Error::Collision));
+        jit.enqueue(now, at(ms(1_200)), ()).unwrap();
+        assert_matches!(jit.enqueue(now, at(ms(1_300)), ()), Err(Error::QueueFull));
+    }
+
+    #[test]
+    fn test_enqueue_across_counter_wrap() {
+        let mut jit = JitQueue::new(3, Chip::SX1301);
+        let wrap = 1 << 32;
+        let now = Duration::from_micros(wrap - 50_000);
+        assert_matches!(jit.enqueue(now, at(ms(100)), ()), Err(Error::TooLate));
+        jit.enqueue(now, at(Duration::from_micros(wrap + 100_000)), ())
+            .unwrap();
+        jit.enqueue(now, at(now + ms(10)), ()).unwrap();
+        // Not confused with a packet at `now + 10ms`, a wrap earlier.
+        jit.enqueue(now, at(now + ms(10) + Duration::from_micros(wrap)), ())
+            .unwrap();
+        assert_eq!(
+            starts(&jit),
+            vec![wrap - 40_000, wrap + 100_000, 2 * wrap - 40_000]
+        );
+    }
+
+    #[test]
+    fn test_radios_collide_on_shared_tx_path() {
+        let on_r1 = |mode| {
+            TxPacket::LoRa(TxPacketLoRa {
+                mode,
+                radio: Radio::R1,
+                ..lora(vec![0; 10])
+            })
+        };
+        let now = ms(1_000);
+        let mut jit = JitQueue::new(4, Chip::SX1301);
+        jit.enqueue(now, at(ms(1_100)), ()).unwrap();
+        assert_matches!(
+            jit.enqueue(now, on_r1(TxMode::Timestamp(ms(1_120))), ()),
+            Err(Error::Collision)
+        );
+        let mut jit = JitQueue::new(4, Chip::SX1302);
+        jit.enqueue(now, at(ms(1_100)), ()).unwrap();
+        jit.enqueue(now, on_r1(TxMode::Timestamp(ms(1_120))), ())
+            .unwrap();
+        jit.enqueue(now, at(now + HANDOFF), ()).unwrap();
+        // Immediate packets only wait for their own radio.
+        jit.enqueue(now, on_r1(TxMode::Immediate), ()).unwrap();
+        assert_eq!(
+            starts(&jit),
+            vec![
+                micros(now + HANDOFF),
+                micros(now + HANDOFF),
+                1_100_000,
+                1_120_000
+            ]
+        );
+    }
+
+    #[test]
+    fn test_immediate_takes_first_gap() {
+        let mut jit = JitQueue::new(4, Chip::SX1301);
+        let now = ms(1_000);
+        jit.enqueue(now, at(now + HANDOFF), 1).unwrap();
+        jit.enqueue(now, at(now + ms(200)), 2).unwrap();
+        jit.enqueue(now, packet(TxMode::Immediate), 3).unwrap();
+        let toa = micros(packet(TxMode::Immediate).time_on_air());
+        let first_end = micros(now + HANDOFF) + toa;
+        assert_eq!(
+            starts(&jit),
+            vec![
+                micros(now + HANDOFF),
+                first_end + micros(GUARD),
+                micros(now + ms(200))
+            ]
+        );
+        match &jit.entries[1].packet {
+            TxPacket::LoRa(pkt) => assert_matches!(pkt.mode, TxMode::Timestamp(_)),
+            TxPacket::FSK(_) => panic!("unexpected FSK packet"),
+        }
+    }
+
+    #[test]
+    fn test_poll_hands_off_when_due() {
+        let sim = configured(SimTraffic {
+            interval: None,
+            ..Default::default()
+        });
+        sim.start().unwrap();
+        let mut jit = JitQueue::new(4, Chip::SX1301);
+        let now = sim.counter().unwrap();
+        jit.enqueue(now, at(now + ms(60)), 1).unwrap();
+        jit.enqueue(now, at(now + ms(200)), 2).unwrap();
+        assert!(jit.poll(&sim).unwrap().is_none());
+        assert!(jit.next_due(now).unwrap() <= ms(30));
+
+        thread::sleep(jit.next_due(sim.counter().unwrap()).unwrap());
+        let (tag, res) = jit.poll(&sim).unwrap().unwrap();
+        assert_eq!(tag, 1);
+        res.unwrap();
+        assert_eq!(sim.transmitted().len(), 1);
+
+        // The second packet isn't due until well after the first has
+        // gone out.
+        assert!(jit.poll(&sim).unwrap().is_none());
+        thread::sleep(jit.next_due(sim.counter().unwrap()).unwrap());
+        assert_eq!(sim.transmit_status(Radio::R0).unwrap(), TxStatus::Free);
+        let (tag, res) = jit.poll(&sim).unwrap().unwrap();
+        assert_eq!(tag, 2);
+        res.unwrap();
+        assert!(jit.is_empty());
+    }
+
+    #[test]
+    fn test_poll_expires_late_packets() {
+        let sim = configured(SimTraffic {
+            interval: None,
+            ..Default::default()
+        });
+        sim.start().unwrap();
+        let mut jit = JitQueue::new(4, Chip::SX1301);
+        let now = sim.counter().unwrap();
+        jit.enqueue(now, at(now + ms(10)), 1).unwrap();
+        thread::sleep(ms(10));
+        let (tag, res) = jit.poll(&sim).unwrap().unwrap();
+        assert_eq!(tag, 1);
+        assert_matches!(res, Err(Error::TooLate));
+        assert!(sim.transmitted().is_empty());
+    }
+}
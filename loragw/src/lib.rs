@@ -3,41 +3,140 @@
 //! This crate provides a high-level interface which serves as
 //! building-block for creating LoRa gateways using the
 //! [SX1301](https://www.semtech.com/products/wireless-rf/lora-gateways/sx1301)
-//! concentrator chip.
+//! and
+//! [SX1302](https://www.semtech.com/products/wireless-rf/lora-gateways/sx1302)
+//! concentrator chips.
+//!
+//! Both chips' HALs may be linked into the same binary, in which case
+//! the chip is selected at runtime with [`Concentrator::open`].
+//!
+//! With the `async` feature, [`ConcentratorHandle`] also offers
+//! received packets as a `Stream` and transmissions as futures.
 
 #[cfg(not(any(feature = "sx1301", feature = "sx1302")))]
-compile_error!("Either feature \"sx1301\" or \"sx1302\" must be enabled for this crate.");
+compile_error!("At least one of features \"sx1301\" or \"sx1302\" must be enabled for this crate.");
 
-#[cfg(all(feature = "sx1301", feature = "sx1302"))]
-compile_error!("Features \"sx1301\" or \"sx1302\" are mutually exclusive.");
+/// Asserts that `$e` matches the pattern `$p`. Stands in for
+/// `assert!(matches!(..))`, which needs a newer toolchain.
+#[cfg(test)]
+macro_rules! assert_matches {
+    ($e:expr, $p:pat) => {
+        match $e {
+            $p => (),
+            _ => panic!(
+                "assertion failed: `{}` does not match `{}`",
+                stringify!($e),
+                stringify!($p)
+            ),
+        }
+    };
+}
 
 #[macro_use]
 mod error;
+mod airtime;
+mod backend;
+mod clock;
+pub mod gps;
+mod handle;
+pub mod jit;
+mod lock;
+pub mod regulatory;
+pub mod reset;
+mod sim;
+#[cfg(feature = "sx1301")]
+mod sx1301;
+#[cfg(feature = "sx1302")]
+mod sx1302;
 mod types;
+pub use crate::backend::*;
+use crate::clock::Clock;
 pub use crate::error::*;
+pub use crate::handle::*;
+use crate::lock::DeviceLock;
+use crate::regulatory::{Region, Regulator};
+pub use crate::sim::*;
 pub use crate::types::*;
 use std::{
-    cell::Cell,
-    convert::{TryFrom, TryInto},
+    cell::{Cell, RefCell},
+    convert::TryInto,
+    ffi::CStr,
     marker::PhantomData,
-    ops,
+    mem, ops,
+    path::Path,
+    process,
     sync::atomic::{AtomicBool, Ordering},
     thread, time,
 };
 
-#[cfg(feature = "sx1301")]
-pub(crate) use libloragw_sx1301_sys as llg;
-
-#[cfg(feature = "sx1302")]
-pub(crate) use libloragw_sx1302_sys as llg;
+/// Calls `$fn` in the HAL module for `$chip`.
+macro_rules! chip_call {
+    ( $chip:expr, $fn:ident ( $($arg:expr),* ) ) => {
+        match $chip {
+            #[cfg(feature = "sx1301")]
+            Chip::SX1301 => crate::sx1301::$fn( $($arg),* ),
+            #[cfg(feature = "sx1302")]
+            Chip::SX1302 => crate::sx1302::$fn( $($arg),* ),
+            // `Concentrator::open` refuses chips which weren't compiled in.
+            #[allow(unreachable_patterns)]
+            chip => unreachable!("support for {} not compiled in", chip),
+        }
+    }
+}
 
-// Ensures we only have 0 or 1 gateway instances opened at a time.
-// This is not a great solution, since another process has its
-// own count.
+// Ensures we only have 0 or 1 gateway instances opened at a time in
+// this process. Other processes are kept out by a `DeviceLock`.
 static GW_IS_OPEN: AtomicBool = AtomicBool::new(false);
 
+/// Lifecycle state of a `Concentrator`.
+///
+/// This trait is sealed; the states are `Unconfigured` and `Running`.
+pub trait State: private::Sealed {
+    /// Whether the concentrator must be stopped when dropped.
+    const RUNNING: bool;
+}
+
+/// State of a `Concentrator` which has not been started, and may be
+/// configured.
+#[derive(Debug)]
+pub enum Unconfigured {}
+
+/// State of a started `Concentrator`, which may receive and transmit.
+#[derive(Debug)]
+pub enum Running {}
+
+impl State for Unconfigured {
+    const RUNNING: bool = false;
+}
+
+impl State for Running {
+    const RUNNING: bool = true;
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for super::Unconfigured {}
+    impl Sealed for super::Running {}
+}
+
 /// A LoRa concentrator.
-pub struct Concentrator {
+///
+/// A concentrator is opened `Unconfigured`, and becomes `Running`
+/// once `start`ed. Configuration is only possible before start, and
+/// receiving and transmitting only after. A running concentrator is
+/// stopped when dropped.
+pub struct Concentrator<S: State = Unconfigured> {
+    /// Which HAL to dispatch to.
+    chip: Chip,
+    /// Keeps other processes from opening the concentrator. Only
+    /// `None` while moving into another state.
+    lock: Option<DeviceLock>,
+    /// Extends the counter to 64 bits. Reset on start.
+    clock: Cell<Clock>,
+    /// Enforces regional limits on transmissions, if a region is
+    /// configured. Kept across restarts.
+    regulator: RefCell<Option<Regulator>>,
+    _state: PhantomData<S>,
     /// Used to prevent `self` from auto implementing `Sync`.
     ///
     /// This is necessary because the `libloragw` makes liberal use of
@@ -45,15 +144,47 @@ pub struct Concentrator {
     _prevent_sync: PhantomData<Cell<()>>,
 }
 
-impl Concentrator {
-    /// Open the spidev-connected concentrator.
-    pub fn open() -> Result<Self> {
+impl Concentrator<Unconfigured> {
+    /// Open the concentrator connected to `spidev_path`.
+    ///
+    /// Returns `Error::Unsupported` if support for `chip` was not
+    /// compiled in, and `Error::Busy` if this or another process
+    /// already has the concentrator open. Processes are excluded with
+    /// an advisory lock named after the SPI device, e.g.
+    /// `/run/lock/loragw-spidev0.0.lock`. Like `connect`, the SX1301
+    /// ignores `spidev_path`.
+    pub fn open(chip: Chip, spidev_path: &CStr) -> Result<Self> {
+        Self::open_with_lock(chip, &lock::lock_path(chip, spidev_path))
+    }
+
+    /// Open the concentrator, excluding other processes with an
+    /// advisory lock on `lock_path` rather than the default.
+    pub fn open_with_lock(chip: Chip, lock_path: &Path) -> Result<Self> {
+        if !chip.is_supported() {
+            log::error!("support for {} not compiled in", chip);
+            return Err(Error::Unsupported);
+        }
         // We can only 'open' one instance
-        if GW_IS_OPEN.compare_and_swap(false, true, Ordering::Acquire) {
+        if GW_IS_OPEN
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
             log::error!("concentrator busy");
-            return Err(Error::Busy);
+            return Err(Error::Busy(Some(process::id())));
         }
+        let lock = match DeviceLock::acquire(lock_path) {
+            Ok(lock) => lock,
+            Err(e) => {
+                GW_IS_OPEN.store(false, Ordering::Release);
+                return Err(e);
+            }
+        };
         Ok(Concentrator {
+            chip,
+            lock: Some(lock),
+            clock: Cell::new(Clock::new()),
+            regulator: RefCell::new(None),
+            _state: PhantomData,
             _prevent_sync: PhantomData,
         })
     }
@@ -61,35 +192,27 @@ impl Concentrator {
     /// Configure the gateway board.
     pub fn config_board(&self, conf: &BoardConf) -> Result {
         log::debug!("conf: {:?}", conf);
-        #[cfg(feature = "sx1301")]
-        unsafe { hal_call!(lgw_board_setconf(conf.into())) }?;
-        #[cfg(feature = "sx1302")]
-        unsafe { hal_call!(lgw_board_setconf(&mut conf.into())) }?;
-        Ok(())
+        chip_call!(self.chip, config_board(conf))
     }
 
     /// Configure an RF chain.
     pub fn config_rx_rf(&self, conf: &RxRFConf) -> Result {
         log::debug!("{:?}", conf);
-        #[cfg(feature = "sx1301")]
-        unsafe { hal_call!(lgw_rxrf_setconf(conf.radio as u8, conf.into())) }?;
-        #[cfg(feature = "sx1302")]
-        unsafe { hal_call!(lgw_rxrf_setconf(conf.radio as u8, &mut conf.into())) }?;
-        Ok(())
+        chip_call!(self.chip, config_rx_rf(conf))
     }
 
     /// Configure an IF chain + modem (must configure before start).
     pub fn config_channel(&self, chain: u8, conf: &ChannelConf) -> Result {
         log::debug!("chain: {}, conf: {:?}", chain, conf);
-        #[cfg(feature = "sx1301")]
-        unsafe { hal_call!(lgw_rxif_setconf(chain, conf.into())) }?;
-        #[cfg(feature = "sx1302")]
-        unsafe { hal_call!(lgw_rxif_setconf(chain, &mut conf.into())) }?;
-        Ok(())
+        chip_call!(self.chip, config_channel(chain, conf))
     }
 
-    /// Configure the Tx gain LUT.
-    pub fn config_tx_gain(&self, gains: &[TxGain]) -> Result {
+    /// Configure the Tx gain LUT of `radio`.
+    ///
+    /// The SX1301 has a single LUT, shared by both radios, which is
+    /// configured for `Radio::R0`. It returns `Error::Unsupported`
+    /// for `Radio::R1`.
+    pub fn config_tx_gain(&self, radio: Radio, gains: &[TxGain]) -> Result {
         if gains.is_empty() || gains.len() > 16 {
             log::error!(
                 "gain table must contain 1 to 16 entries, {} provided",
@@ -97,135 +220,244 @@ impl Concentrator {
             );
             return Err(Error::Size);
         }
-        log::debug!("gains: {:?}", gains);
-        let mut lut = TxGainLUT::default();
-        lut.lut[..gains.len()].clone_from_slice(gains);
-        lut.size = gains.len() as u8;
-        unsafe {
-            hal_call!(lgw_txgain_setconf(
-                // TODO: de-hardcode
-                #[cfg(feature = "sx1302")]
-                0,
-                &mut lut as *mut TxGainLUT as *mut llg::lgw_tx_gain_lut_s
-            ))
-        }?;
+        log::debug!("{:?} gains: {:?}", radio, gains);
+        chip_call!(self.chip, config_tx_gain(radio, gains))
+    }
+
+    /// Configure listen-before-talk (must configure before start).
+    ///
+    /// While LBT is enabled, only LoRa packets on one of
+    /// `conf.channels` may be transmitted, and only with
+    /// `TxMode::Timestamp` or `TxMode::PPS`. Transmissions the HAL
+    /// denies fail with `Error::LBT`. Only the SX1301 supports LBT.
+    ///
+    /// With `conf.enable` false LBT is disabled, and `conf.channels`
+    /// may be empty.
+    pub fn config_lbt(&self, conf: &LBTConf) -> Result {
+        if (conf.enable && conf.channels.is_empty()) || conf.channels.len() > 8 {
+            log::error!(
+                "LBT must have 1 to 8 channels, {} provided",
+                conf.channels.len()
+            );
+            return Err(Error::Size);
+        }
+        log::debug!("conf: {:?}", conf);
+        chip_call!(self.chip, config_lbt(conf))
+    }
+
+    /// Enforce `region`'s duty cycle and dwell time limits on
+    /// transmissions, or lift them with `None`.
+    ///
+    /// Transmissions exceeding them fail with `Error::DutyCycle` or
+    /// `Error::DwellTime`. Time on air is tracked from the first
+    /// transmission after `region` is configured, and is kept across
+    /// restarts unless the region changes.
+    pub fn config_region(&self, region: Option<Region>) -> Result {
+        log::debug!("region: {:?}", region);
+        let mut regulator = self.regulator.borrow_mut();
+        if regulator.as_ref().map(Regulator::region) != region {
+            *regulator = region.map(Regulator::new);
+        }
         Ok(())
     }
 
-    /// according to previously set parameters.
-    pub fn start(&self) -> Result {
+    /// Attempt to connect to concentrator.
+    ///
+    /// This function is intended to check if we the concentrator chip
+    /// exists and is the correct version.
+    ///
+    /// `spidev_path` is ignored by the SX1301 HAL, which opens a
+    /// compiled-in path.
+    pub fn connect(&self, spidev_path: &CStr) -> Result {
+        chip_call!(self.chip, connect(spidev_path))
+    }
+
+    /// Start the LoRa concentrator according to previously set
+    /// parameters.
+    ///
+    /// The concentrator is closed if it fails to start.
+    pub fn start(self) -> Result<Concentrator<Running>> {
         log::info!("starting concentrator");
-        unsafe { hal_call!(lgw_start()) }?;
-        Ok(())
+        chip_call!(self.chip, start())?;
+        // The counter restarts from zero.
+        self.clock.set(Clock::new());
+        Ok(self.into_state())
     }
+}
 
+impl Concentrator<Running> {
     /// Stop the LoRa concentrator and disconnect it.
-    pub fn stop(&self) -> Result {
+    ///
+    /// The concentrator is closed if it fails to stop.
+    pub fn stop(self) -> Result<Concentrator<Unconfigured>> {
         log::info!("stopping concentrator");
-        unsafe { hal_call!(lgw_stop()) }?;
-        Ok(())
+        let stopped = self.into_state();
+        chip_call!(stopped.chip, stop())?;
+        Ok(stopped)
     }
 
-    /// Returns the concentrators current receive status.
-    pub fn receive_status(&self) -> Result<RxStatus> {
+    /// Returns the current receive status of `radio`.
+    pub fn receive_status(&self, radio: Radio) -> Result<RxStatus> {
         const RX_STATUS: u8 = 2;
-        let mut rx_status = 0xFE;
-        #[cfg(feature = "sx1301")]
-        unsafe { hal_call!(lgw_status(RX_STATUS, &mut rx_status)) }?;
-        #[cfg(feature = "sx1302")]
-        unsafe {
-            hal_call!(lgw_status(
-                {
-                    log::warn!("remove hardcoded RF chain argument from status calls");
-                    0u8
-                },
-                RX_STATUS,
-                &mut rx_status
-            ))
-        }?;
-        rx_status.try_into()
+        chip_call!(self.chip, status(radio, RX_STATUS))?.try_into()
     }
 
     /// Perform a non-blocking read of up to 16 packets from
     /// concentrator's FIFO.
     pub fn receive(&self) -> Result<Option<Vec<RxPacket>>> {
-        let mut tmp_buf: [llg::lgw_pkt_rx_s; 16] = [Default::default(); 16];
-        let len = unsafe { hal_call!(lgw_receive(tmp_buf.len() as u8, tmp_buf.as_mut_ptr())) }?;
-        if len > 0 {
-            let mut out = Vec::new();
-            for tmp in tmp_buf[..len].iter() {
-                out.push(RxPacket::try_from(tmp)?);
-            }
-            Ok(Some(out))
-        } else {
-            Ok(None)
+        let mut packets = chip_call!(self.chip, receive())?;
+        if packets.is_empty() {
+            return Ok(None);
+        }
+        let clock = self.clock.get();
+        for pkt in &mut packets {
+            let timestamp = pkt.timestamp_mut();
+            *timestamp = time::Duration::from_micros(clock.extend(timestamp.as_micros() as u32));
         }
+        Ok(Some(packets))
     }
 
     /// Transmit `packet` over the air.
-    pub fn transmit(&self, packet: TxPacket) -> Result {
-        while self.transmit_status()? != TxStatus::Free {
+    ///
+    /// Blocks until the transmitter of the packet's radio is free.
+    /// Use `transmit_status`, or a `jit::JitQueue`, to avoid waiting.
+    ///
+    /// `TxMode::Timestamp` packets fail with `Error::TooLate` if their
+    /// time has passed, and with `Error::TooEarly` if it is 2^31 µs or
+    /// more ahead of the counter. With a region configured, packets
+    /// exceeding its limits fail, see `config_region`.
+    pub fn transmit(&self, mut packet: TxPacket) -> Result {
+        while self.transmit_status(packet.radio())? != TxStatus::Free {
             const SLEEP_TIME: time::Duration = time::Duration::from_millis(5);
             log::trace!("transmitter is busy, sleeping for {:?}", SLEEP_TIME);
             thread::sleep(SLEEP_TIME);
         }
-        #[cfg(feature = "sx1301")]
-        unsafe { hal_call!(lgw_send(packet.try_into()?)) }?;
-        #[cfg(feature = "sx1302")]
-        unsafe { hal_call!(lgw_send(&mut packet.try_into()?)) }?;
+        let mut regulator = self.regulator.borrow_mut();
+        let mut start = time::Instant::now();
+        match packet.mode_mut() {
+            TxMode::Timestamp(target) => {
+                let now = self.counter()?.as_micros() as u64;
+                let count = clock::tx_count(now, target.as_micros() as u64)?;
+                start += time::Duration::from_micros(target.as_micros() as u64 - now);
+                *target = time::Duration::from_micros(u64::from(count));
+            }
+            TxMode::PPS(delay) => start += *delay,
+            TxMode::Immediate => (),
+        }
+        if let Some(regulator) = regulator.as_ref() {
+            regulator.check(&packet, start)?;
+        }
+        chip_call!(self.chip, send(packet.clone()))?;
+        if let Some(regulator) = regulator.as_mut() {
+            regulator.record(&packet, start);
+        }
         Ok(())
     }
 
-    /// Attempt to connect to concentrator.
+    /// Returns the current transmit status of `radio`.
     ///
-    /// This function is intended to check if we the concentrator chip
-    /// exists and is the correct version.
-    #[cfg(feature = "sx1301")]
-    pub fn connect(&self) -> Result {
-        unsafe { hal_call!(lgw_connect(false, 0)) }?;
-        Ok(())
+    /// `transmit` only hands a packet to the HAL once this is
+    /// `TxStatus::Free` for the packet's radio, which lets callers
+    /// check before calling it. The SX1301 has one TX path, whose
+    /// status is reported for either radio.
+    pub fn transmit_status(&self, radio: Radio) -> Result<TxStatus> {
+        const TX_STATUS: u8 = 1;
+        chip_call!(self.chip, status(radio, TX_STATUS))?.try_into()
     }
 
-    /// Attempt to connect to concentrator.
+    /// Returns the current value of the concentrator's internal 1 MHz
+    /// counter.
     ///
-    /// This function is intended to check if we the concentrator chip
-    /// exists and is the correct version.
-    #[cfg(feature = "sx1302")]
-    pub fn connect(&self, spidev_path: &::std::ffi::CStr) -> Result {
-        unsafe { hal_call!(lgw_connect(spidev_path.as_ptr())) }?;
-        Ok(())
+    /// This is the clock `RxPacket` timestamps and
+    /// `TxMode::Timestamp` targets are expressed in. The hardware
+    /// counter is 32 bits wide and wraps roughly every 71.6 minutes,
+    /// which is tracked to return a 64-bit value counting from start.
+    pub fn counter(&self) -> Result<time::Duration> {
+        let count_us = chip_call!(self.chip, counter())?;
+        let mut clock = self.clock.get();
+        let now = clock.update(count_us);
+        self.clock.set(clock);
+        Ok(time::Duration::from_micros(now))
+    }
+
+    /// Returns the value the internal counter had at the last PPS edge
+    /// from the GPS receiver.
+    ///
+    /// Only meaningful with a GPS receiver connected, to relate its
+    /// time solutions to the counter. Extended to 64 bits like
+    /// `counter`.
+    pub fn pps_counter(&self) -> Result<time::Duration> {
+        let count_us = chip_call!(self.chip, pps_counter())?;
+        let pps = self.clock.get().extend(count_us);
+        Ok(time::Duration::from_micros(pps))
+    }
+
+    /// Abort any scheduled or in-flight transmission.
+    pub fn abort_tx(&self) -> Result {
+        log::debug!("aborting TX");
+        chip_call!(self.chip, abort_tx())
+    }
+
+    /// Abort a scheduled or in-flight transmission on `radio` only.
+    ///
+    /// Only the SX1302 has a TX path per RF chain. The SX1301 returns
+    /// `Error::Unsupported`; use `abort_tx` instead.
+    pub fn abort_tx_on(&self, radio: Radio) -> Result {
+        log::debug!("aborting TX on {:?}", radio);
+        chip_call!(self.chip, abort_tx_on(radio))
+    }
+
+    /// Returns the board temperature, in degrees Celsius.
+    ///
+    /// Only SX1302 boards carry a temperature sensor. The SX1301
+    /// returns `Error::Unsupported`.
+    pub fn temperature(&self) -> Result<f32> {
+        chip_call!(self.chip, temperature())
     }
 }
 
-// Private functions.
-impl Concentrator {
-    /// Returns the concentrators current transmit status.
+impl<S: State> Concentrator<S> {
+    /// Returns the chip this concentrator was opened for.
+    pub fn chip(&self) -> Chip {
+        self.chip
+    }
+
+    /// Returns the HAL version, and the chip and FPGA versions if
+    /// available.
     ///
-    /// We keep this private since `transmit` uses it internally, and
-    /// it may lead to confusion about who's responsibility it is to
-    /// check TX status.
-    fn transmit_status(&self) -> Result<TxStatus> {
-        const TX_STATUS: u8 = 1;
-        let mut tx_status = 0xFE;
-        #[cfg(feature = "sx1301")]
-        unsafe { hal_call!(lgw_status(TX_STATUS, &mut tx_status)) }?;
-        #[cfg(feature = "sx1302")]
-        unsafe {
-            hal_call!(lgw_status(
-                {
-                    log::warn!("remove hardcoded RF chain argument from status calls");
-                    0u8
-                },
-                TX_STATUS,
-                &mut tx_status
-            ))
-        }?;
-        tx_status.try_into()
+    /// Hardware versions are only read once the concentrator has been
+    /// connected or started, and are `None` before then.
+    pub fn version_info(&self) -> Result<VersionInfo> {
+        chip_call!(self.chip, version_info())
+    }
+
+    /// Moves this open concentrator into state `T`, without closing
+    /// or stopping it.
+    fn into_state<T: State>(mut self) -> Concentrator<T> {
+        let chip = self.chip;
+        let lock = self.lock.take();
+        let clock = self.clock.get();
+        let regulator = self.regulator.replace(None);
+        mem::forget(self);
+        Concentrator {
+            chip,
+            lock,
+            clock: Cell::new(clock),
+            regulator: RefCell::new(regulator),
+            _state: PhantomData,
+            _prevent_sync: PhantomData,
+        }
     }
 }
 
-impl ops::Drop for Concentrator {
+impl<S: State> ops::Drop for Concentrator<S> {
     fn drop(&mut self) {
+        if S::RUNNING {
+            log::info!("stopping concentrator");
+            if let Err(e) = chip_call!(self.chip, stop()) {
+                log::error!("failed to stop concentrator: {}", e);
+            }
+        }
         log::info!("closing concentrator");
         GW_IS_OPEN.store(false, Ordering::Release);
     }
@@ -235,30 +467,47 @@ impl ops::Drop for Concentrator {
 mod tests {
     use super::*;
     use lazy_static::lazy_static;
-    use std::sync::Mutex;
+    use std::{env, sync::Mutex};
 
     lazy_static! {
         static ref TEST_MUTEX: Mutex<()> = Mutex::new(());
     }
 
+    /// Opens the concentrator with its lock in the temp dir, as
+    /// `/run/lock` may not be writable.
+    fn open() -> Result<Concentrator> {
+        let lock_path = env::temp_dir().join(format!("loragw-{}.lock", process::id()));
+        Concentrator::open_with_lock(Chip::default(), &lock_path)
+    }
+
     #[test]
     fn test_open_close_succeeds() {
         let _lock = TEST_MUTEX.lock().unwrap();
         assert!(!GW_IS_OPEN.load(Ordering::Relaxed));
         {
-            let _gw = Concentrator::open().unwrap();
+            let _gw = open().unwrap();
             assert!(GW_IS_OPEN.load(Ordering::Relaxed));
             // _gw `drop`ped here
         }
         assert!(!GW_IS_OPEN.load(Ordering::Relaxed));
     }
 
+    #[test]
+    fn test_parse_hal_version() {
+        assert_eq!(VersionInfo::parse_hal("Version: 5.0.1;"), "5.0.1");
+        assert_eq!(
+            VersionInfo::parse_hal("Options: spi;Version: 2.0.1;"),
+            "2.0.1"
+        );
+        assert_eq!(VersionInfo::parse_hal("unexpected"), "unexpected");
+    }
+
     #[test]
     fn test_double_open_fails() {
         let _lock = TEST_MUTEX.lock().unwrap();
         assert!(!GW_IS_OPEN.load(Ordering::Relaxed));
-        let _gw1 = Concentrator::open().unwrap();
+        let _gw1 = open().unwrap();
         assert!(GW_IS_OPEN.load(Ordering::Relaxed));
-        assert!(Concentrator::open().is_err());
+        assert!(open().is_err());
     }
 }
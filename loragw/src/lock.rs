@@ -0,0 +1,139 @@
+//! Exclusive, cross-process claim on the concentrator.
+//!
+//! `libloragw` keeps its state in globals and assumes it is the only
+//! user of the SPI bus, so two processes driving the same board
+//! corrupt each other's state. A `DeviceLock` holds an advisory
+//! `flock` on a lock file for as long as it lives, and records the
+//! holder's PID in it so other processes can report who owns the
+//! board. Lock files are named after the SPI device, so boards on
+//! different devices can be used at the same time.
+
+use crate::{
+    error::{Error, Result},
+    Chip,
+};
+use std::{
+    ffi::{CStr, OsStr},
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::unix::{ffi::OsStrExt, io::AsRawFd},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Directory `Concentrator::open` takes its lock file in.
+const LOCK_DIR: &str = "/run/lock";
+
+/// `SPI_DEV_PATH` from the SX1301 HAL's `loragw_spi.native.c`. The
+/// SX1301 HAL always opens this path, whatever spidev path is
+/// configured.
+const SX1301_SPIDEV_PATH: &str = "/dev/spidev0.0";
+
+/// Returns the lock file for a `chip` concentrator on `spidev_path`,
+/// e.g. `/run/lock/loragw-spidev0.0.lock`.
+pub(crate) fn lock_path(chip: Chip, spidev_path: &CStr) -> PathBuf {
+    let spidev_path = match chip {
+        Chip::SX1301 => Path::new(SX1301_SPIDEV_PATH),
+        Chip::SX1302 => Path::new(OsStr::from_bytes(spidev_path.to_bytes())),
+    };
+    let mut name = OsStr::new("loragw-").to_os_string();
+    name.push(
+        spidev_path
+            .file_name()
+            .unwrap_or_else(|| OsStr::new("spidev")),
+    );
+    name.push(".lock");
+    Path::new(LOCK_DIR).join(name)
+}
+
+/// An advisory lock on a lock file, released when dropped.
+#[derive(Debug)]
+pub(crate) struct DeviceLock {
+    _file: File,
+}
+
+impl DeviceLock {
+    /// Takes the lock at `path`, creating the file if needed.
+    ///
+    /// Returns `Error::Busy` with the holder's PID, if known, when
+    /// another open file (in this or another process) holds it.
+    pub(crate) fn acquire(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            // The holder's PID must survive until we hold the lock.
+            .truncate(false)
+            .open(path)
+            .map_err(|e| {
+                log::error!("failed to open lock file {:?}: {}", path, e);
+                e
+            })?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::WouldBlock {
+                log::error!("failed to lock {:?}: {}", path, err);
+                return Err(err.into());
+            }
+            let pid = holder(&mut file);
+            match pid {
+                Some(pid) => log::error!("concentrator busy, locked by process {}", pid),
+                None => log::error!("concentrator busy, locked by unknown process"),
+            }
+            return Err(Error::Busy(pid));
+        }
+        file.set_len(0)?;
+        write!(file, "{}", process::id())?;
+        Ok(DeviceLock { _file: file })
+    }
+}
+
+/// Returns the PID recorded in a lock file, if any.
+///
+/// The holder writes its PID just after taking the lock, so this may
+/// briefly be empty.
+fn holder(file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, ffi::CString, fs};
+
+    #[test]
+    fn test_lock_path_names_spidev() {
+        let path = CString::new("/dev/spidev1.0").unwrap();
+        assert_eq!(
+            lock_path(Chip::SX1302, &path),
+            Path::new("/run/lock/loragw-spidev1.0.lock")
+        );
+        // The SX1301 HAL always opens spidev0.0.
+        assert_eq!(
+            lock_path(Chip::SX1301, &path),
+            Path::new("/run/lock/loragw-spidev0.0.lock")
+        );
+    }
+
+    #[test]
+    fn test_second_lock_reports_holder() {
+        let path = env::temp_dir().join(format!("loragw-test-{}.lock", process::id()));
+        {
+            let _lock = DeviceLock::acquire(&path).unwrap();
+            assert_eq!(
+                fs::read_to_string(&path).unwrap(),
+                process::id().to_string()
+            );
+            match DeviceLock::acquire(&path) {
+                Err(Error::Busy(pid)) => assert_eq!(pid, Some(process::id())),
+                res => panic!("unexpected result {:?}", res),
+            }
+        }
+        // Released when dropped.
+        DeviceLock::acquire(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,307 @@
+//! Regional limits on transmissions.
+//!
+//! Some regions cap the share of time a transmitter may spend on the
+//! air in each sub-band (duty cycle), others how long a single
+//! transmission may last (dwell time). A `Regulator` tracks the time
+//! on air of past transmissions per sub-band over a sliding `WINDOW`,
+//! and tells whether another transmission would exceed its region's
+//! limits.
+
+use crate::{error::Error, Result, TxPacket};
+use std::{
+    collections::VecDeque,
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// Period over which duty cycles are measured.
+pub const WINDOW: Duration = Duration::from_secs(3600);
+
+/// A regulatory region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// Europe, 863 to 870 MHz. Duty cycle limited per sub-band.
+    EU868,
+    /// North America, 902 to 928 MHz. Transmissions may last at most
+    /// 400 ms.
+    US915,
+    /// Australia, 915 to 928 MHz.
+    AU915,
+    /// Asia, 923 MHz group 1. Transmissions may last at most 400 ms.
+    AS923_1,
+    /// India, 865 to 867 MHz.
+    IN865,
+    /// South Korea, 920 to 923 MHz.
+    KR920,
+}
+
+/// A range of frequencies sharing a duty cycle.
+#[derive(Debug)]
+struct SubBand {
+    /// Lowest center frequency in the sub-band, in Hz.
+    min_freq: u32,
+    /// Highest center frequency in the sub-band, in Hz.
+    max_freq: u32,
+    /// Time on air allowed per `WINDOW`.
+    max_airtime: Duration,
+}
+
+impl SubBand {
+    /// Returns a sub-band allowing `duty_cycle_permille` ‰ of
+    /// `WINDOW` on the air.
+    const fn new(min_freq: u32, max_freq: u32, duty_cycle_permille: u64) -> Self {
+        SubBand {
+            min_freq,
+            max_freq,
+            max_airtime: Duration::from_millis(WINDOW.as_secs() * duty_cycle_permille),
+        }
+    }
+}
+
+/// ETSI EN 300 220 sub-bands, as used by LoRaWAN.
+const EU868_SUB_BANDS: [SubBand; 6] = [
+    SubBand::new(863_000_000, 865_000_000, 1),
+    SubBand::new(865_000_000, 868_000_000, 10),
+    SubBand::new(868_000_000, 868_600_000, 10),
+    SubBand::new(868_700_000, 869_200_000, 1),
+    SubBand::new(869_400_000, 869_650_000, 100),
+    SubBand::new(869_700_000, 870_000_000, 10),
+];
+
+impl Region {
+    /// Returns the sub-bands duty cycles are tracked for, or an empty
+    /// slice if this region has no duty cycle limits.
+    fn sub_bands(self) -> &'static [SubBand] {
+        match self {
+            Region::EU868 => &EU868_SUB_BANDS,
+            _ => &[],
+        }
+    }
+
+    /// Returns the longest a single transmission may last, if limited.
+    pub fn dwell_time(self) -> Option<Duration> {
+        match self {
+            Region::US915 | Region::AS923_1 => Some(Duration::from_millis(400)),
+            _ => None,
+        }
+    }
+
+    /// Returns the duty cycle, in ‰, allowed at `freq`, or `None` if
+    /// it isn't limited.
+    ///
+    /// Frequencies outside all of a duty cycle limited region's
+    /// sub-bands are allowed no time on air.
+    pub fn duty_cycle_permille(self, freq: u32) -> Option<u64> {
+        if self.sub_bands().is_empty() {
+            return None;
+        }
+        Some(self.sub_band(freq).map_or(0, |idx| {
+            self.sub_bands()[idx].max_airtime.as_millis() as u64 / WINDOW.as_secs()
+        }))
+    }
+
+    /// Returns the index of the sub-band containing `freq`.
+    fn sub_band(self, freq: u32) -> Option<usize> {
+        self.sub_bands()
+            .iter()
+            .position(|band| band.min_freq <= freq && freq <= band.max_freq)
+    }
+}
+
+impl FromStr for Region {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "EU868" | "eu868" => Region::EU868,
+            "US915" | "us915" => Region::US915,
+            "AU915" | "au915" => Region::AU915,
+            "AS923-1" | "as923-1" => Region::AS923_1,
+            "IN865" | "in865" => Region::IN865,
+            "KR920" | "kr920" => Region::KR920,
+            invalid => {
+                log::error!("unable to convert {:?} to Region", invalid);
+                return Err(Error::Data);
+            }
+        })
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Region::EU868 => "EU868",
+            Region::US915 => "US915",
+            Region::AU915 => "AU915",
+            Region::AS923_1 => "AS923-1",
+            Region::IN865 => "IN865",
+            Region::KR920 => "KR920",
+        })
+    }
+}
+
+/// A past transmission counted against its sub-band's duty cycle.
+#[derive(Debug)]
+struct Sent {
+    sub_band: usize,
+    start: Instant,
+    airtime: Duration,
+}
+
+/// Enforces a region's limits on transmissions.
+#[derive(Debug)]
+pub struct Regulator {
+    region: Region,
+    /// Transmissions which started less than `WINDOW` ago, or are
+    /// scheduled to start, oldest first.
+    sent: VecDeque<Sent>,
+}
+
+impl Regulator {
+    /// Returns a regulator for `region` which has seen no
+    /// transmissions.
+    pub fn new(region: Region) -> Self {
+        Regulator {
+            region,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Returns the region this regulator enforces the limits of.
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Returns the time on air counted against the sub-band
+    /// containing `freq` for a transmission starting at `start`, or
+    /// `None` if it isn't in a duty cycle limited sub-band.
+    pub fn airtime(&self, freq: u32, start: Instant) -> Option<Duration> {
+        let sub_band = self.region.sub_band(freq)?;
+        Some(
+            self.sent
+                .iter()
+                .filter(|sent| sent.sub_band == sub_band && sent.start + WINDOW > start)
+                .map(|sent| sent.airtime)
+                .sum(),
+        )
+    }
+
+    /// Checks whether `packet` may be transmitted at `start`.
+    ///
+    /// Fails with `Error::DwellTime` if the packet's time on air
+    /// exceeds the region's dwell time, and with `Error::DutyCycle`
+    /// if it would exceed its sub-band's duty cycle over the `WINDOW`
+    /// around `start`.
+    pub fn check(&self, packet: &TxPacket, start: Instant) -> Result {
+        let airtime = packet.time_on_air();
+        if let Some(dwell_time) = self.region.dwell_time() {
+            if airtime > dwell_time {
+                log::warn!(
+                    "TX of {:?} exceeds {} dwell time of {:?}",
+                    airtime,
+                    self.region,
+                    dwell_time
+                );
+                return Err(Error::DwellTime);
+            }
+        }
+        let sub_bands = self.region.sub_bands();
+        if sub_bands.is_empty() {
+            return Ok(());
+        }
+        let freq = packet.freq();
+        let sub_band = match self.region.sub_band(freq) {
+            Some(idx) => &sub_bands[idx],
+            None => {
+                log::warn!("{} Hz is outside all {} sub-bands", freq, self.region);
+                return Err(Error::DutyCycle);
+            }
+        };
+        let used = self.airtime(freq, start).unwrap_or_default();
+        if used + airtime > sub_band.max_airtime {
+            log::warn!(
+                "{} TX of {:?} at {} Hz exceeds duty cycle, {:?} of {:?} used",
+                self.region,
+                airtime,
+                freq,
+                used,
+                sub_band.max_airtime
+            );
+            return Err(Error::DutyCycle);
+        }
+        Ok(())
+    }
+
+    /// Counts `packet`, transmitted at `start`, against its
+    /// sub-band's duty cycle.
+    pub fn record(&mut self, packet: &TxPacket, start: Instant) {
+        self.sent.retain(|sent| sent.start + WINDOW > start);
+        if let Some(sub_band) = self.region.sub_band(packet.freq()) {
+            self.sent.push_back(Sent {
+                sub_band,
+                start,
+                airtime: packet.time_on_air(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sim::test_util::lora, Spreading, TxPacketLoRa};
+
+    fn lora_tx(freq: u32, spreading: Spreading, payload_len: usize) -> TxPacket {
+        TxPacket::LoRa(TxPacketLoRa {
+            freq,
+            spreading,
+            ..lora(vec![0; payload_len])
+        })
+    }
+
+    #[test]
+    fn test_duty_cycle_slides() {
+        let mut regulator = Regulator::new(Region::EU868);
+        // 2.47 s on air, against 0.1% of an hour in 868.7 to 869.2 MHz.
+        let pkt = lora_tx(868_800_000, Spreading::SF12, 51);
+        assert_eq!(pkt.time_on_air(), Duration::from_micros(2_465_792));
+        let t0 = Instant::now();
+        regulator.check(&pkt, t0).unwrap();
+        regulator.record(&pkt, t0);
+        let start = t0 + Duration::from_secs(10);
+        assert_matches!(regulator.check(&pkt, start), Err(Error::DutyCycle));
+        // Other sub-bands are tracked separately.
+        regulator
+            .check(&lora_tx(868_100_000, Spreading::SF12, 51), start)
+            .unwrap();
+        // The first transmission no longer counts an hour after it.
+        assert_eq!(
+            regulator.airtime(868_800_000, start),
+            Some(pkt.time_on_air())
+        );
+        assert_eq!(
+            regulator.airtime(868_800_000, t0 + WINDOW),
+            Some(Duration::from_secs(0))
+        );
+        regulator.check(&pkt, t0 + WINDOW).unwrap();
+        assert_matches!(
+            regulator.check(&lora_tx(870_100_000, Spreading::SF7, 1), start),
+            Err(Error::DutyCycle)
+        );
+    }
+
+    #[test]
+    fn test_dwell_time() {
+        let regulator = Regulator::new(Region::US915);
+        let now = Instant::now();
+        regulator
+            .check(&lora_tx(902_300_000, Spreading::SF10, 11), now)
+            .unwrap();
+        assert_matches!(
+            regulator.check(&lora_tx(902_300_000, Spreading::SF10, 51), now),
+            Err(Error::DwellTime)
+        );
+        assert_eq!(Region::US915.duty_cycle_permille(902_300_000), None);
+        assert_eq!(Region::EU868.duty_cycle_permille(869_525_000), Some(100));
+    }
+}
@@ -0,0 +1,172 @@
+//! Hardware reset of the concentrator over a GPIO line.
+//!
+//! Concentrator boards must be reset before `lgw_start`, which the
+//! HAL leaves to the host. [`reset`] drives the board's reset line
+//! through either the legacy sysfs interface or a GPIO character
+//! device.
+
+use crate::error::Result;
+use std::{
+    fs::{self, File},
+    io,
+    os::{
+        raw::{c_int, c_ulong},
+        unix::io::{AsRawFd, FromRawFd},
+    },
+    path::{Path, PathBuf},
+    thread, time,
+};
+
+/// Identifies a GPIO line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GpioLine {
+    /// A line exported through `/sys/class/gpio`, by its global
+    /// number.
+    Sysfs(u32),
+    /// A line on a GPIO character device, e.g. `/dev/gpiochip0`.
+    Chip {
+        /// Path to the character device.
+        path: PathBuf,
+        /// Offset of the line on the chip.
+        offset: u32,
+    },
+}
+
+/// Describes how to reset a concentrator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResetConf {
+    /// Line connected to the concentrator's reset pin.
+    pub line: GpioLine,
+    /// The line resets the concentrator when low, rather than high.
+    pub active_low: bool,
+    /// How long to hold the concentrator in reset.
+    pub pulse: time::Duration,
+    /// How long to wait before asserting reset, and after releasing
+    /// it.
+    pub settle: time::Duration,
+}
+
+/// Resets the concentrator by pulsing `conf.line`.
+///
+/// The line is driven inactive, then asserted for `conf.pulse` and
+/// released again, waiting `conf.settle` before and after.
+pub fn reset(conf: &ResetConf) -> Result {
+    log::info!("resetting concentrator with {:?}", conf);
+    // Released, i.e. high when active low.
+    let line = Output::open(&conf.line, conf.active_low)?;
+    let set = |active: bool| line.set(active != conf.active_low);
+    thread::sleep(conf.settle);
+    set(true)?;
+    thread::sleep(conf.pulse);
+    set(false)?;
+    thread::sleep(conf.settle);
+    Ok(())
+}
+
+/// A GPIO line configured as an output.
+enum Output {
+    /// Path to the line's sysfs `value` file.
+    Sysfs(PathBuf),
+    /// A line handle returned by `GPIO_GET_LINEHANDLE_IOCTL`.
+    Chip(File),
+}
+
+// GPIO character device ABI (v1), from `linux/gpio.h`.
+const GPIOHANDLES_MAX: usize = 64;
+const GPIOHANDLE_REQUEST_OUTPUT: u32 = 1 << 1;
+// _IOWR(0xB4, 0x03, struct gpiohandle_request)
+const GPIO_GET_LINEHANDLE_IOCTL: c_ulong = 0xC16C_B403;
+// _IOWR(0xB4, 0x09, struct gpiohandle_data)
+const GPIOHANDLE_SET_LINE_VALUES_IOCTL: c_ulong = 0xC040_B409;
+
+#[repr(C)]
+struct GpioHandleRequest {
+    lineoffsets: [u32; GPIOHANDLES_MAX],
+    flags: u32,
+    default_values: [u8; GPIOHANDLES_MAX],
+    consumer_label: [u8; 32],
+    lines: u32,
+    fd: c_int,
+}
+
+#[repr(C)]
+struct GpioHandleData {
+    values: [u8; GPIOHANDLES_MAX],
+}
+
+impl Output {
+    /// Configures `line` as an output driven high if `high`, else
+    /// low.
+    fn open(line: &GpioLine, high: bool) -> Result<Self> {
+        match line {
+            GpioLine::Sysfs(number) => Self::open_sysfs(*number, high),
+            GpioLine::Chip { path, offset } => Self::open_chip(path, *offset, high),
+        }
+    }
+
+    fn open_sysfs(number: u32, high: bool) -> Result<Self> {
+        let dir = PathBuf::from(format!("/sys/class/gpio/gpio{}", number));
+        if !dir.exists() {
+            fs::write("/sys/class/gpio/export", number.to_string()).map_err(|e| {
+                log::error!("failed to export GPIO {}: {}", number, e);
+                e
+            })?;
+        }
+        // Sets the direction and initial value at once.
+        let direction = if high { "high" } else { "low" };
+        fs::write(dir.join("direction"), direction).map_err(|e| {
+            log::error!("failed to make GPIO {} an output: {}", number, e);
+            e
+        })?;
+        Ok(Output::Sysfs(dir.join("value")))
+    }
+
+    fn open_chip(path: &Path, offset: u32, high: bool) -> Result<Self> {
+        let chip = File::open(path).map_err(|e| {
+            log::error!("failed to open GPIO chip {:?}: {}", path, e);
+            e
+        })?;
+        let mut req = GpioHandleRequest {
+            lineoffsets: [0; GPIOHANDLES_MAX],
+            flags: GPIOHANDLE_REQUEST_OUTPUT,
+            default_values: [0; GPIOHANDLES_MAX],
+            consumer_label: [0; 32],
+            lines: 1,
+            fd: -1,
+        };
+        req.lineoffsets[0] = offset;
+        req.default_values[0] = high as u8;
+        let label = b"concentrate-reset";
+        req.consumer_label[..label.len()].copy_from_slice(label);
+        if unsafe { libc::ioctl(chip.as_raw_fd(), GPIO_GET_LINEHANDLE_IOCTL, &mut req) } < 0 {
+            let err = io::Error::last_os_error();
+            log::error!("failed to request line {} of {:?}: {}", offset, path, err);
+            return Err(err.into());
+        }
+        Ok(Output::Chip(unsafe { File::from_raw_fd(req.fd) }))
+    }
+
+    /// Drives the line high if `high`, else low.
+    fn set(&self, high: bool) -> Result {
+        match self {
+            Output::Sysfs(value) => fs::write(value, if high { "1" } else { "0" })?,
+            Output::Chip(handle) => {
+                let mut data = GpioHandleData {
+                    values: [0; GPIOHANDLES_MAX],
+                };
+                data.values[0] = high as u8;
+                let res = unsafe {
+                    libc::ioctl(
+                        handle.as_raw_fd(),
+                        GPIOHANDLE_SET_LINE_VALUES_IOCTL,
+                        &mut data,
+                    )
+                };
+                if res < 0 {
+                    return Err(io::Error::last_os_error().into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,983 @@
+use crate::{
+    clock, error,
+    regulatory::{Region, Regulator},
+    Bandwidth, BoardConf, CRCCheck, ChannelConf, Chip, Coderate, ConcentratorBackend,
+    ConfigurableBackend, HalError, HalErrorKind, LBTConf, Radio, Result, RxPacket, RxPacketFSK,
+    RxPacketLoRa, RxRFConf, Spreading, TxGain, TxMode, TxPacket, TxStatus, VersionInfo,
+};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    thread, time,
+};
+
+/// Maximum number of packets returned by a single call to `receive`,
+/// mirroring the hardware FIFO.
+const RX_FIFO_SIZE: usize = 16;
+
+/// Board temperature reported until `set_temperature` is called, in
+/// degrees Celsius.
+const DEFAULT_TEMPERATURE: f32 = 25.0;
+
+/// Spreading factors a simulated multirate channel cycles through.
+const MULTIRATE_SPREADING: [Spreading; 6] = [
+    Spreading::SF7,
+    Spreading::SF8,
+    Spreading::SF9,
+    Spreading::SF10,
+    Spreading::SF11,
+    Spreading::SF12,
+];
+
+/// Describes the receive traffic generated by a `SimConcentrator`.
+#[derive(Debug, Clone)]
+pub struct SimTraffic {
+    /// Time between generated packets.
+    ///
+    /// Packets are spread round-robin over all enabled channels. Use
+    /// `None` to only deliver packets queued with
+    /// `SimConcentrator::inject`.
+    pub interval: Option<time::Duration>,
+    /// Length of generated payloads.
+    pub payload_len: usize,
+    /// RSSI reported for generated packets, in dB.
+    pub rssi: f32,
+    /// SNR reported for generated packets, in dB.
+    pub snr: f32,
+}
+
+impl Default for SimTraffic {
+    fn default() -> Self {
+        SimTraffic {
+            interval: Some(time::Duration::from_secs(1)),
+            payload_len: 16,
+            rssi: -80.0,
+            snr: 7.5,
+        }
+    }
+}
+
+#[derive(Default)]
+struct SimState {
+    board: Option<BoardConf>,
+    radios: [Option<RxRFConf>; 2],
+    channels: BTreeMap<u8, ChannelConf>,
+    gains: [Vec<TxGain>; 2],
+    lbt: Option<LBTConf>,
+    /// LBT channel frequencies currently occupied by other users.
+    lbt_busy: BTreeSet<u32>,
+    regulator: Option<Regulator>,
+    started: Option<time::Instant>,
+    generated: u64,
+    injected: VecDeque<RxPacket>,
+    transmitted: Vec<TxPacket>,
+    /// Per-radio time (since start) at which the most recently
+    /// transmitted packet starts going out over the air.
+    tx_start: [Option<time::Duration>; 2],
+    /// Per-radio time (since start) at which the most recently
+    /// transmitted packet finishes going out over the air.
+    tx_busy_until: [Option<time::Duration>; 2],
+    temperature: Option<f32>,
+}
+
+/// A pure-software concentrator.
+///
+/// `SimConcentrator` requires no hardware. It accepts the same
+/// configuration as a real concentrator, produces synthetic receive
+/// traffic on the configured channels, and records every packet
+/// handed to `transmit`.
+///
+/// Transmissions occupy their radio for the packet's time on air, so
+/// they can be aborted until they finish. With LBT enabled,
+/// transmissions are checked the way the SX1301 HAL checks them, and
+/// channels can be marked busy with `set_lbt_busy`. With a region
+/// configured, its limits are enforced like `Concentrator` does.
+pub struct SimConcentrator {
+    chip: Chip,
+    traffic: SimTraffic,
+    state: RefCell<SimState>,
+}
+
+impl SimConcentrator {
+    /// Returns a new simulated `chip` concentrator generating
+    /// `traffic`.
+    pub fn new(chip: Chip, traffic: SimTraffic) -> Self {
+        SimConcentrator {
+            chip,
+            traffic,
+            state: RefCell::new(SimState::default()),
+        }
+    }
+
+    /// Start the simulated concentrator.
+    ///
+    /// Unlike `Concentrator`, a simulated concentrator is used in the
+    /// same way before and after start. `ConfigurableBackend::start`
+    /// returns it boxed.
+    pub fn start(&self) -> Result {
+        log::info!("starting simulated concentrator");
+        let mut state = self.state.borrow_mut();
+        if state.started.is_some() {
+            return Err(Self::hal_error(
+                "lgw_start",
+                HalErrorKind::State,
+                "concentrator already started".into(),
+            ));
+        }
+        state.started = Some(time::Instant::now());
+        Ok(())
+    }
+
+    /// Stop the simulated concentrator, aborting pending
+    /// transmissions.
+    ///
+    /// Configuration, injected packets, and regional limits are kept,
+    /// and the counter restarts from zero on the next start.
+    pub fn stop(&self) -> Result {
+        log::info!("stopping simulated concentrator");
+        self.abort_tx()?;
+        let mut state = self.state.borrow_mut();
+        state.started = None;
+        state.generated = 0;
+        state.tx_start = [None; 2];
+        state.tx_busy_until = [None; 2];
+        Ok(())
+    }
+
+    /// Returns the chip this concentrator simulates.
+    pub fn chip(&self) -> Chip {
+        self.chip
+    }
+
+    /// Queue `packet` to be returned by the next call to `receive`.
+    pub fn inject(&self, packet: RxPacket) {
+        self.state.borrow_mut().injected.push_back(packet);
+    }
+
+    /// Returns all packets transmitted so far, oldest first.
+    ///
+    /// Packets aborted before they finished are not included.
+    pub fn transmitted(&self) -> Vec<TxPacket> {
+        self.state.borrow().transmitted.clone()
+    }
+
+    /// Returns the most recently configured board configuration.
+    pub fn board_conf(&self) -> Option<BoardConf> {
+        self.state.borrow().board.clone()
+    }
+
+    /// Returns the most recently configured Tx gain LUT of `radio`.
+    pub fn tx_gains(&self, radio: Radio) -> Vec<TxGain> {
+        self.state.borrow().gains[radio as usize].clone()
+    }
+
+    /// Mark the LBT channel at `freq` as occupied (or free) by
+    /// other users, causing transmissions on it to be denied.
+    pub fn set_lbt_busy(&self, freq: u32, busy: bool) {
+        let mut state = self.state.borrow_mut();
+        if busy {
+            state.lbt_busy.insert(freq);
+        } else {
+            state.lbt_busy.remove(&freq);
+        }
+    }
+
+    /// Set the board temperature, in degrees Celsius, reported from
+    /// now on.
+    pub fn set_temperature(&self, celsius: f32) {
+        self.state.borrow_mut().temperature = Some(celsius);
+    }
+
+    /// Applies the SX1301 HAL's LBT rules to a transmission of
+    /// `packet` starting `lead` from now.
+    fn check_lbt(
+        lbt: &LBTConf,
+        busy: &BTreeSet<u32>,
+        packet: &TxPacket,
+        lead: time::Duration,
+    ) -> Result {
+        let pkt = match packet {
+            TxPacket::LoRa(pkt) => pkt,
+            TxPacket::FSK(_) => {
+                log::warn!("sim LBT denied FSK transmission");
+                return Err(error::Error::LBT);
+            }
+        };
+        if let TxMode::Immediate = pkt.mode {
+            return Err(Self::hal_error(
+                "lgw_send",
+                HalErrorKind::Config,
+                "immediate transmissions are not supported with LBT enabled".into(),
+            ));
+        }
+        let long_scan = |chan: &crate::LBTChanConf| chan.scan_time_us == 5000;
+        // A 125 kHz packet must be on an LBT channel, a 250 kHz one
+        // centered between two adjacent channels 200 kHz apart.
+        let (selected, max_time) = match pkt.bandwidth {
+            Bandwidth::BW125kHz => match lbt.channels.iter().find(|c| c.freq == pkt.freq) {
+                Some(chan) if long_scan(chan) => (vec![chan.freq], 4_000_000),
+                Some(chan) => (vec![chan.freq], 400_000),
+                None => (vec![], 0),
+            },
+            Bandwidth::BW250kHz => match lbt.channels.windows(2).find(|pair| {
+                pair[1].freq == pair[0].freq + 200_000 && pkt.freq == pair[0].freq + 100_000
+            }) {
+                Some(pair) if long_scan(&pair[0]) => (vec![pair[0].freq, pair[1].freq], 4_000_000),
+                Some(pair) => (vec![pair[0].freq, pair[1].freq], 200_000),
+                None => (vec![], 0),
+            },
+            _ => (vec![], 0),
+        };
+        if selected.is_empty() {
+            log::warn!("sim LBT denied transmission outside LBT channels");
+            return Err(error::Error::LBT);
+        }
+        if selected.iter().any(|freq| busy.contains(freq)) {
+            log::warn!("sim LBT denied transmission on busy channel");
+            return Err(error::Error::LBT);
+        }
+        // The channel must have been free for the entire time from now
+        // until the packet finishes, less the HAL's 2048 uS margin.
+        if (lead + packet.time_on_air()).as_micros() as u64 >= max_time - 2048 {
+            log::warn!("sim LBT denied transmission exceeding max TX time");
+            return Err(error::Error::LBT);
+        }
+        Ok(())
+    }
+
+    /// Returns the error the HAL fails `call` with, and logs it.
+    fn hal_error(call: &'static str, kind: HalErrorKind, context: String) -> error::Error {
+        let err = HalError {
+            call,
+            kind,
+            code: -1,
+            context,
+        };
+        log::error!("sim {}", err);
+        err.into()
+    }
+
+    /// Fails configuration `call` if the simulated concentrator is
+    /// running. Like `Concentrator`, it must be configured before
+    /// start.
+    fn check_stopped(&self, call: &'static str) -> Result {
+        if self.state.borrow().started.is_some() {
+            return Err(Self::hal_error(
+                call,
+                HalErrorKind::State,
+                "must be configured before start".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns elapsed time since `start`, failing `call` if the
+    /// simulated concentrator is not running.
+    fn elapsed(state: &SimState, call: &'static str) -> Result<time::Duration> {
+        match state.started {
+            Some(started) => Ok(started.elapsed()),
+            None => Err(Self::hal_error(
+                call,
+                HalErrorKind::State,
+                "concentrator is not started".into(),
+            )),
+        }
+    }
+
+    /// Synthesize the `n`th generated packet, or `None` if there are
+    /// no enabled channels to receive on.
+    fn generate(&self, state: &SimState, n: u64, timestamp: time::Duration) -> Option<RxPacket> {
+        let channels: Vec<(&u8, &ChannelConf)> = state
+            .channels
+            .iter()
+            .filter(|(_, conf)| match conf {
+                ChannelConf::Disable => false,
+                _ => true,
+            })
+            .collect();
+        if channels.is_empty() {
+            return None;
+        }
+        let (&if_chain, conf) = channels[(n % channels.len() as u64) as usize];
+        let payload: Vec<u8> = n
+            .to_le_bytes()
+            .iter()
+            .cycle()
+            .take(self.traffic.payload_len)
+            .cloned()
+            .collect();
+        let freq = |radio: Radio, offset: i32| {
+            let center = state.radios[radio as usize]
+                .as_ref()
+                .map_or(0, |rf| i64::from(rf.freq));
+            (center + i64::from(offset)) as u32
+        };
+        Some(match *conf {
+            ChannelConf::Disable => unreachable!(),
+            ChannelConf::Fixed {
+                radio,
+                freq: offset,
+                bandwidth,
+                spreading,
+            } => RxPacket::LoRa(self.lora(
+                freq(radio, offset),
+                if_chain,
+                radio,
+                bandwidth,
+                spreading,
+                timestamp,
+                payload,
+            )),
+            ChannelConf::Multirate {
+                radio,
+                freq: offset,
+            } => RxPacket::LoRa(self.lora(
+                freq(radio, offset),
+                if_chain,
+                radio,
+                Bandwidth::BW125kHz,
+                MULTIRATE_SPREADING
+                    [(n / channels.len() as u64) as usize % MULTIRATE_SPREADING.len()],
+                timestamp,
+                payload,
+            )),
+            ChannelConf::FSK {
+                radio,
+                freq: offset,
+                datarate,
+                ..
+            } => RxPacket::FSK(RxPacketFSK {
+                freq: freq(radio, offset),
+                if_chain,
+                crc_check: CRCCheck::Pass,
+                timestamp,
+                radio,
+                datarate,
+                rssi: self.traffic.rssi,
+                crc: 0,
+                payload,
+            }),
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn lora(
+        &self,
+        freq: u32,
+        if_chain: u8,
+        radio: Radio,
+        bandwidth: Bandwidth,
+        spreading: Spreading,
+        timestamp: time::Duration,
+        payload: Vec<u8>,
+    ) -> RxPacketLoRa {
+        RxPacketLoRa {
+            freq,
+            if_chain,
+            crc_check: CRCCheck::Pass,
+            timestamp,
+            utc: None,
+            gps_time: None,
+            radio,
+            bandwidth,
+            spreading,
+            coderate: Coderate::Cr4_5,
+            rssi: self.traffic.rssi,
+            snr: self.traffic.snr,
+            snr_min: self.traffic.snr,
+            snr_max: self.traffic.snr,
+            crc: 0,
+            payload,
+        }
+    }
+}
+
+impl Default for SimConcentrator {
+    fn default() -> Self {
+        SimConcentrator::new(Chip::default(), SimTraffic::default())
+    }
+}
+
+impl ConfigurableBackend for SimConcentrator {
+    fn config_board(&self, conf: &BoardConf) -> Result {
+        self.check_stopped("lgw_board_setconf")?;
+        log::debug!("sim conf: {:?}", conf);
+        self.state.borrow_mut().board = Some(conf.clone());
+        Ok(())
+    }
+
+    fn config_rx_rf(&self, conf: &RxRFConf) -> Result {
+        self.check_stopped("lgw_rxrf_setconf")?;
+        log::debug!("sim {:?}", conf);
+        self.state.borrow_mut().radios[conf.radio as usize] = Some(conf.clone());
+        Ok(())
+    }
+
+    fn config_channel(&self, chain: u8, conf: &ChannelConf) -> Result {
+        self.check_stopped("lgw_rxif_setconf")?;
+        log::debug!("sim chain: {}, conf: {:?}", chain, conf);
+        self.state.borrow_mut().channels.insert(chain, conf.clone());
+        Ok(())
+    }
+
+    fn config_tx_gain(&self, radio: Radio, gains: &[TxGain]) -> Result {
+        if gains.is_empty() || gains.len() > 16 {
+            log::error!(
+                "gain table must contain 1 to 16 entries, {} provided",
+                gains.len()
+            );
+            return Err(error::Error::Size);
+        }
+        self.check_stopped("lgw_txgain_setconf")?;
+        log::debug!("sim {:?} gains: {:?}", radio, gains);
+        self.state.borrow_mut().gains[radio as usize] = gains.to_vec();
+        Ok(())
+    }
+
+    fn config_lbt(&self, conf: &LBTConf) -> Result {
+        if (conf.enable && conf.channels.is_empty()) || conf.channels.len() > 8 {
+            log::error!(
+                "LBT must have 1 to 8 channels, {} provided",
+                conf.channels.len()
+            );
+            return Err(error::Error::Size);
+        }
+        self.check_stopped("lgw_lbt_setconf")?;
+        log::debug!("sim conf: {:?}", conf);
+        self.state.borrow_mut().lbt = Some(conf.clone());
+        Ok(())
+    }
+
+    fn config_region(&self, region: Option<Region>) -> Result {
+        self.check_stopped("config_region")?;
+        log::debug!("sim region: {:?}", region);
+        let mut state = self.state.borrow_mut();
+        if state.regulator.as_ref().map(Regulator::region) != region {
+            state.regulator = region.map(Regulator::new);
+        }
+        Ok(())
+    }
+
+    fn start(self: Box<Self>) -> Result<Box<dyn ConcentratorBackend>> {
+        SimConcentrator::start(&self)?;
+        Ok(self)
+    }
+}
+
+impl ConcentratorBackend for SimConcentrator {
+    fn receive(&self) -> Result<Option<Vec<RxPacket>>> {
+        let mut state = self.state.borrow_mut();
+        let elapsed = Self::elapsed(&state, "lgw_receive")?;
+        let mut out = Vec::new();
+        while out.len() < RX_FIFO_SIZE {
+            match state.injected.pop_front() {
+                Some(pkt) => out.push(pkt),
+                None => break,
+            }
+        }
+        if let Some(interval) = self.traffic.interval {
+            let interval_us = interval.as_micros().max(1) as u64;
+            while out.len() < RX_FIFO_SIZE {
+                let due = (state.generated + 1) * interval_us;
+                if due > elapsed.as_micros() as u64 {
+                    break;
+                }
+                let n = state.generated;
+                state.generated += 1;
+                if let Some(pkt) = self.generate(&state, n, time::Duration::from_micros(due)) {
+                    out.push(pkt);
+                }
+            }
+        }
+        if out.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(out))
+        }
+    }
+
+    fn transmit(&self, packet: TxPacket) -> Result {
+        let mut state = self.state.borrow_mut();
+        let mut elapsed = Self::elapsed(&state, "lgw_send")?;
+        let (len, mode) = match &packet {
+            TxPacket::LoRa(pkt) => (pkt.payload.len(), pkt.mode),
+            TxPacket::FSK(pkt) => (pkt.payload.len(), pkt.mode),
+        };
+        if len > 256 {
+            log::error!("attempt to send {} byte payload", len);
+            return Err(error::Error::Size);
+        }
+        let radio = packet.radio() as usize;
+        // Like hardware, wait for the previous transmission on this
+        // radio to finish.
+        if let Some(busy_until) = state.tx_busy_until[radio] {
+            if busy_until > elapsed {
+                log::trace!("sim transmitter is busy, sleeping");
+                thread::sleep(busy_until - elapsed);
+                elapsed = busy_until;
+            }
+        }
+        let start = match mode {
+            TxMode::Immediate => elapsed,
+            TxMode::Timestamp(ts) => {
+                // Rejected like `Concentrator::transmit` does.
+                clock::tx_count(elapsed.as_micros() as u64, ts.as_micros() as u64)?;
+                ts
+            }
+            TxMode::PPS(delay) => elapsed + delay,
+        };
+        if let Some(lbt) = state.lbt.as_ref().filter(|lbt| lbt.enable) {
+            Self::check_lbt(lbt, &state.lbt_busy, &packet, start - elapsed)?;
+        }
+        // Regional limits are tracked in host time, like
+        // `Concentrator::transmit` does.
+        let start_at = time::Instant::now() + (start - elapsed);
+        if let Some(regulator) = &mut state.regulator {
+            regulator.check(&packet, start_at)?;
+            regulator.record(&packet, start_at);
+        }
+        log::debug!("sim transmitting {:?}", packet);
+        state.tx_start[radio] = Some(start);
+        state.tx_busy_until[radio] = Some(start + packet.time_on_air());
+        state.transmitted.push(packet);
+        Ok(())
+    }
+
+    fn transmit_status(&self, radio: Radio) -> Result<TxStatus> {
+        let state = self.state.borrow();
+        let elapsed = Self::elapsed(&state, "lgw_status")?;
+        // Like the SX1302, each radio has its own TX path.
+        let radio = radio as usize;
+        Ok(match (state.tx_start[radio], state.tx_busy_until[radio]) {
+            (_, Some(end)) if end <= elapsed => TxStatus::Free,
+            (Some(start), Some(_)) if start <= elapsed => TxStatus::Transmitting,
+            (_, Some(_)) => TxStatus::Scheduled,
+            _ => TxStatus::Free,
+        })
+    }
+
+    fn counter(&self) -> Result<time::Duration> {
+        // Already as extended by `Concentrator::counter`.
+        Self::elapsed(&self.state.borrow(), "lgw_get_instcnt")
+    }
+
+    /// The simulated PPS fires on every whole second since start.
+    fn pps_counter(&self) -> Result<time::Duration> {
+        let elapsed = Self::elapsed(&self.state.borrow(), "lgw_get_trigcnt")?;
+        Ok(time::Duration::from_secs(elapsed.as_secs()))
+    }
+
+    fn abort_tx(&self) -> Result {
+        self.abort_tx_on(Radio::R0)?;
+        self.abort_tx_on(Radio::R1)
+    }
+
+    fn abort_tx_on(&self, radio: Radio) -> Result {
+        let mut state = self.state.borrow_mut();
+        let elapsed = Self::elapsed(&state, "lgw_abort_tx")?;
+        match state.tx_busy_until[radio as usize].take() {
+            // The most recent packet on `radio` is the only one which
+            // may still be pending.
+            Some(busy_until) if busy_until > elapsed => {
+                let idx = state
+                    .transmitted
+                    .iter()
+                    .rposition(|pkt| pkt.radio() as usize == radio as usize)
+                    .expect("busy radio has no transmitted packet");
+                let pkt = state.transmitted.remove(idx);
+                log::debug!("sim aborted {:?}", pkt);
+            }
+            _ => log::debug!("sim {:?} has no pending TX to abort", radio),
+        }
+        Ok(())
+    }
+
+    fn temperature(&self) -> Result<f32> {
+        let state = self.state.borrow();
+        Self::elapsed(&state, "lgw_get_temperature")?;
+        Ok(state.temperature.unwrap_or(DEFAULT_TEMPERATURE))
+    }
+
+    fn version_info(&self) -> Result<VersionInfo> {
+        Ok(VersionInfo {
+            hal: format!("sim {}", env!("CARGO_PKG_VERSION")),
+            chip: self.chip,
+            chip_version: None,
+            fpga_version: None,
+        })
+    }
+
+    fn stop(self: Box<Self>) -> Result<Box<dyn ConfigurableBackend>> {
+        SimConcentrator::stop(&self)?;
+        Ok(self)
+    }
+}
+
+/// Fixtures shared by the crate's tests.
+#[cfg(test)]
+pub(crate) mod test_util {
+    use super::*;
+    use crate::{RadioType, TxPacketLoRa};
+
+    /// Returns an SX1301 simulator generating `traffic`, with radio 0
+    /// at 916.3 MHz and multirate channels 0 to 3 at 916.0, 916.2,
+    /// 916.4 and 916.6 MHz.
+    pub(crate) fn configured(traffic: SimTraffic) -> SimConcentrator {
+        let sim = SimConcentrator::new(Chip::SX1301, traffic);
+        sim.config_rx_rf(&RxRFConf {
+            radio: Radio::R0,
+            enable: true,
+            freq: 916_300_000,
+            rssi_offset: -169.0,
+            type_: RadioType::SX1257,
+            tx_enable: true,
+            tx_notch_freq: 0,
+        })
+        .unwrap();
+        for (i, offset) in [-300_000, -100_000, 100_000, 300_000].iter().enumerate() {
+            sim.config_channel(
+                i as u8,
+                &ChannelConf::Multirate {
+                    radio: Radio::R0,
+                    freq: *offset,
+                },
+            )
+            .unwrap();
+        }
+        sim
+    }
+
+    /// Returns an immediate 14 dBm SF7 125 kHz packet at 916.1 MHz on
+    /// radio 0.
+    pub(crate) fn lora(payload: Vec<u8>) -> TxPacketLoRa {
+        TxPacketLoRa {
+            freq: 916_100_000,
+            mode: TxMode::Immediate,
+            radio: Radio::R0,
+            power: 14,
+            bandwidth: Bandwidth::BW125kHz,
+            spreading: Spreading::SF7,
+            coderate: Coderate::Cr4_5,
+            invert_polarity: false,
+            preamble: None,
+            omit_crc: false,
+            implicit_header: false,
+            payload,
+        }
+    }
+
+    /// Returns `lora(payload)` as a `TxPacket`.
+    pub(crate) fn lora_tx(payload: Vec<u8>) -> TxPacket {
+        TxPacket::LoRa(lora(payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{test_util::*, *};
+
+    #[test]
+    fn test_receive_before_start_fails() {
+        let sim = configured(SimTraffic::default());
+        assert!(sim.receive().is_err());
+        assert!(sim.transmit(lora_tx(vec![0])).is_err());
+    }
+
+    #[test]
+    fn test_hal_errors_name_call() {
+        let sim = configured(SimTraffic::default());
+        match sim.counter() {
+            Err(error::Error::HAL(err)) => {
+                assert_eq!(
+                    err,
+                    HalError {
+                        call: "lgw_get_instcnt",
+                        kind: HalErrorKind::State,
+                        code: -1,
+                        context: "concentrator is not started".into(),
+                    }
+                );
+                assert_eq!(
+                    err.to_string(),
+                    "HAL call lgw_get_instcnt failed (state error, code -1): \
+                     concentrator is not started"
+                );
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+        sim.start().unwrap();
+        assert_matches!(
+            sim.start(),
+            Err(error::Error::HAL(HalError {
+                call: "lgw_start",
+                kind: HalErrorKind::State,
+                ..
+            }))
+        );
+    }
+
+    #[test]
+    fn test_generated_traffic_uses_configured_channels() {
+        let sim = configured(SimTraffic {
+            interval: Some(time::Duration::from_micros(1)),
+            payload_len: 4,
+            ..Default::default()
+        });
+        sim.start().unwrap();
+        std::thread::sleep(time::Duration::from_millis(1));
+        let packets = sim.receive().unwrap().unwrap();
+        assert_eq!(packets.len(), RX_FIFO_SIZE);
+        for (n, pkt) in packets.iter().enumerate() {
+            match pkt {
+                RxPacket::LoRa(pkt) => {
+                    assert_eq!(usize::from(pkt.if_chain), n % 4);
+                    assert_eq!(
+                        pkt.freq,
+                        [916_000_000, 916_200_000, 916_400_000, 916_600_000][n % 4]
+                    );
+                    assert_eq!(pkt.payload, (n as u64).to_le_bytes()[..4].to_vec());
+                }
+                RxPacket::FSK(_) => panic!("unexpected FSK packet"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_injected_packets_are_received() {
+        let sim = configured(SimTraffic {
+            interval: None,
+            ..Default::default()
+        });
+        sim.start().unwrap();
+        assert!(sim.receive().unwrap().is_none());
+        sim.inject(RxPacket::FSK(RxPacketFSK {
+            freq: 868_800_000,
+            if_chain: 9,
+            crc_check: CRCCheck::Pass,
+            timestamp: time::Duration::from_micros(42),
+            radio: Radio::R1,
+            datarate: 50_000,
+            rssi: -42.0,
+            crc: 0,
+            payload: vec![1, 2, 3],
+        }));
+        let packets = sim.receive().unwrap().unwrap();
+        assert_eq!(packets.len(), 1);
+        assert!(sim.receive().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_config_fails_after_start() {
+        let sim = configured(SimTraffic::default());
+        sim.start().unwrap();
+        let radio = sim.state.borrow().radios[0].clone().unwrap();
+        let channel = sim.state.borrow().channels[&0].clone();
+        let results = vec![
+            sim.config_board(&BoardConf {
+                lorawan_public: true,
+                clksrc: Radio::R0,
+                spidev_path: Default::default(),
+            }),
+            sim.config_rx_rf(&radio),
+            sim.config_channel(0, &channel),
+            sim.config_tx_gain(Radio::R0, &[TxGain::default()]),
+            sim.config_lbt(&LBTConf {
+                enable: false,
+                rssi_target: -80,
+                rssi_offset: 0,
+                channels: Vec::new(),
+            }),
+            sim.config_region(Some(Region::US915)),
+        ];
+        for res in results {
+            assert_matches!(
+                res,
+                Err(error::Error::HAL(HalError {
+                    kind: HalErrorKind::State,
+                    ..
+                }))
+            );
+        }
+        assert!(sim.state.borrow().regulator.is_none());
+        sim.stop().unwrap();
+        sim.config_region(Some(Region::US915)).unwrap();
+    }
+
+    #[test]
+    fn test_counter_runs_after_start() {
+        let sim = configured(SimTraffic::default());
+        assert!(sim.counter().is_err());
+        sim.start().unwrap();
+        let before = sim.counter().unwrap();
+        std::thread::sleep(time::Duration::from_millis(2));
+        assert!(sim.counter().unwrap() >= before + time::Duration::from_millis(2));
+    }
+
+    #[test]
+    fn test_pps_counter_latches_whole_seconds() {
+        let sim = configured(SimTraffic::default());
+        sim.start().unwrap();
+        assert_eq!(sim.pps_counter().unwrap(), time::Duration::from_secs(0));
+        assert!(sim.counter().unwrap() > sim.pps_counter().unwrap());
+    }
+
+    #[test]
+    fn test_version_info_reports_chip() {
+        let sim = SimConcentrator::new(Chip::SX1302, SimTraffic::default());
+        assert_eq!(sim.version_info().unwrap().chip, Chip::SX1302);
+    }
+
+    #[test]
+    fn test_temperature_can_be_set() {
+        let sim = configured(SimTraffic::default());
+        sim.start().unwrap();
+        assert_eq!(sim.temperature().unwrap(), DEFAULT_TEMPERATURE);
+        sim.set_temperature(71.5);
+        assert_eq!(sim.temperature().unwrap(), 71.5);
+    }
+
+    #[test]
+    fn test_transmit_status_is_per_radio() {
+        let sim = configured(SimTraffic::default());
+        sim.start().unwrap();
+        sim.transmit(lora_tx(vec![0; 32])).unwrap();
+        assert_ne!(sim.transmit_status(Radio::R0).unwrap(), TxStatus::Free);
+        assert_eq!(sim.transmit_status(Radio::R1).unwrap(), TxStatus::Free);
+    }
+
+    #[test]
+    fn test_abort_pending_transmission() {
+        let sim = configured(SimTraffic::default());
+        sim.start().unwrap();
+        sim.transmit(lora_tx(vec![1])).unwrap();
+        let mut pkt = lora_tx(vec![2]);
+        if let TxPacket::LoRa(ref mut pkt) = pkt {
+            pkt.mode = TxMode::Timestamp(time::Duration::from_secs(60));
+        }
+        sim.transmit(pkt).unwrap();
+        assert_eq!(sim.transmitted().len(), 2);
+        sim.abort_tx().unwrap();
+        let sent = sim.transmitted();
+        assert_eq!(sent.len(), 1);
+        match &sent[0] {
+            TxPacket::LoRa(pkt) => assert_eq!(pkt.payload, vec![1]),
+            TxPacket::FSK(_) => panic!("unexpected FSK packet"),
+        }
+        // Nothing left to abort.
+        sim.abort_tx_on(Radio::R0).unwrap();
+        assert_eq!(sim.transmitted().len(), 1);
+    }
+
+    #[test]
+    fn test_region_limits_transmissions() {
+        let sim = configured(SimTraffic::default());
+        sim.config_region(Some(Region::US915)).unwrap();
+        sim.start().unwrap();
+        let mut pkt = lora_tx(vec![0; 51]);
+        if let TxPacket::LoRa(ref mut pkt) = pkt {
+            pkt.spreading = Spreading::SF10;
+        }
+        assert_matches!(sim.transmit(pkt.clone()), Err(error::Error::DwellTime));
+        sim.transmit(lora_tx(vec![0; 51])).unwrap();
+        assert_eq!(sim.transmitted().len(), 1);
+        // Lifted along with the region.
+        sim.stop().unwrap();
+        sim.config_region(None).unwrap();
+        sim.start().unwrap();
+        sim.transmit(pkt).unwrap();
+    }
+
+    #[test]
+    fn test_stop_drops_pending_transmissions() {
+        let sim = configured(SimTraffic::default());
+        sim.start().unwrap();
+        let mut pkt = lora_tx(vec![1]);
+        if let TxPacket::LoRa(ref mut pkt) = pkt {
+            pkt.mode = TxMode::Timestamp(time::Duration::from_secs(60));
+        }
+        sim.transmit(pkt).unwrap();
+        sim.stop().unwrap();
+        assert!(sim.transmitted().is_empty());
+        assert!(sim.counter().is_err());
+        // Restarts with the same configuration, and a fresh counter.
+        sim.start().unwrap();
+        assert!(sim.counter().unwrap() < time::Duration::from_secs(1));
+        assert_eq!(sim.transmit_status(Radio::R0).unwrap(), TxStatus::Free);
+    }
+
+    #[test]
+    fn test_lbt_denials() {
+        let sim = configured(SimTraffic::default());
+        let chan = |freq| crate::LBTChanConf {
+            freq,
+            scan_time_us: 128,
+        };
+        sim.config_lbt(&LBTConf {
+            enable: true,
+            rssi_target: -80,
+            rssi_offset: 0,
+            channels: vec![chan(916_100_000), chan(916_300_000)],
+        })
+        .unwrap();
+        sim.start().unwrap();
+        // Returns a packet starting shortly after the radio is free.
+        let at = |freq, bandwidth, payload_len| {
+            while sim.transmit_status(Radio::R0).unwrap() != TxStatus::Free {
+                std::thread::sleep(time::Duration::from_millis(1));
+            }
+            let mut pkt = lora_tx(vec![0; payload_len]);
+            if let TxPacket::LoRa(ref mut pkt) = pkt {
+                pkt.freq = freq;
+                pkt.bandwidth = bandwidth;
+                pkt.mode =
+                    TxMode::Timestamp(sim.counter().unwrap() + time::Duration::from_millis(5));
+            }
+            pkt
+        };
+        // Immediate transmissions are refused outright.
+        assert_matches!(
+            sim.transmit(lora_tx(vec![0])),
+            Err(error::Error::HAL(HalError {
+                kind: HalErrorKind::Config,
+                ..
+            }))
+        );
+        sim.transmit(at(916_100_000, Bandwidth::BW125kHz, 1))
+            .unwrap();
+        sim.transmit(at(916_200_000, Bandwidth::BW250kHz, 1))
+            .unwrap();
+        assert_matches!(
+            sim.transmit(at(916_500_000, Bandwidth::BW125kHz, 1)),
+            Err(error::Error::LBT)
+        );
+        // Longer than the 400 ms a 128 uS scan allows.
+        let mut long = at(916_100_000, Bandwidth::BW125kHz, 64);
+        if let TxPacket::LoRa(ref mut pkt) = long {
+            pkt.spreading = Spreading::SF10;
+        }
+        assert_matches!(sim.transmit(long), Err(error::Error::LBT));
+        sim.set_lbt_busy(916_300_000, true);
+        assert_matches!(
+            sim.transmit(at(916_200_000, Bandwidth::BW250kHz, 1)),
+            Err(error::Error::LBT)
+        );
+        sim.set_lbt_busy(916_300_000, false);
+        sim.transmit(at(916_300_000, Bandwidth::BW125kHz, 1))
+            .unwrap();
+        assert_eq!(sim.transmitted().len(), 3);
+    }
+
+    #[test]
+    fn test_transmissions_are_recorded() {
+        let sim = configured(SimTraffic::default());
+        sim.start().unwrap();
+        sim.transmit(lora_tx(vec![1, 2, 3])).unwrap();
+        assert!(sim.transmit(lora_tx(vec![0; 257])).is_err());
+        let sent = sim.transmitted();
+        assert_eq!(sent.len(), 1);
+        match &sent[0] {
+            TxPacket::LoRa(pkt) => assert_eq!(pkt.payload, vec![1, 2, 3]),
+            TxPacket::FSK(_) => panic!("unexpected FSK packet"),
+        }
+    }
+}
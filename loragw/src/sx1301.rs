@@ -0,0 +1,465 @@
+//! Conversions and HAL calls specific to the SX1301 `libloragw`.
+
+use crate::{
+    error::{Error, Result},
+    types::{MOD_FSK, MOD_LORA},
+    Bandwidth, BoardConf, CRCCheck, ChannelConf, Chip, Coderate, LBTConf, Radio, RxPacket,
+    RxPacketFSK, RxPacketLoRa, RxRFConf, Spreading, TxGain, TxPacket, VersionInfo,
+};
+use libloragw_sx1301_sys as llg;
+use std::{
+    convert::{TryFrom, TryInto},
+    ffi::CStr,
+    time,
+};
+
+/// Returned by `lgw_send` when LBT denies a transmission.
+const LGW_LBT_ISSUE: usize = 1;
+
+/// `LGW_VERSION` from `loragw_reg.h`.
+const LGW_VERSION: u16 = 2;
+
+/// `LGW_FPGA_VERSION` from `loragw_fpga.h`.
+const LGW_FPGA_VERSION: u16 = 3;
+
+/// `LGW_TIMESTAMP` from `loragw_reg.h`.
+const LGW_TIMESTAMP: u16 = 289;
+
+/// `LGW_GPS_EN` from `loragw_reg.h`.
+const LGW_GPS_EN: u16 = 316;
+
+/// FPGA versions the HAL supports (`FPGA_VERSION` in `loragw_reg.c`).
+///
+/// Boards without an FPGA return garbage for its version register,
+/// so like the HAL we only trust these values.
+const SUPPORTED_FPGA_VERSIONS: [u8; 2] = [31, 33];
+
+fn spreading_to_hal(spreading: Spreading) -> Result<u32> {
+    Ok(match spreading {
+        Spreading::Undefined => 0x00,
+        Spreading::SF7 => 0x02,
+        Spreading::SF8 => 0x04,
+        Spreading::SF9 => 0x08,
+        Spreading::SF10 => 0x10,
+        Spreading::SF11 => 0x20,
+        Spreading::SF12 => 0x40,
+        Spreading::Multi => 0x7E,
+        unsupported => {
+            log::error!("{:?} is not supported by the SX1301", unsupported);
+            return Err(Error::Unsupported);
+        }
+    })
+}
+
+fn spreading_from_hal(other: u32) -> Result<Spreading> {
+    Ok(match other {
+        0x00 => Spreading::Undefined,
+        0x02 => Spreading::SF7,
+        0x04 => Spreading::SF8,
+        0x08 => Spreading::SF9,
+        0x10 => Spreading::SF10,
+        0x20 => Spreading::SF11,
+        0x40 => Spreading::SF12,
+        0x7E => Spreading::Multi,
+        invalid => {
+            log::error!("unable to convert {:?} to Spreading", invalid);
+            return Err(Error::Data);
+        }
+    })
+}
+
+fn bandwidth_to_hal(bandwidth: Bandwidth) -> u8 {
+    match bandwidth {
+        Bandwidth::Undefined => 0,
+        Bandwidth::BW500kHz => 0x01,
+        Bandwidth::BW250kHz => 0x02,
+        Bandwidth::BW125kHz => 0x03,
+        Bandwidth::BW62_5kHz => 0x04,
+        Bandwidth::BW31_2kHz => 0x05,
+        Bandwidth::BW15_6kHz => 0x06,
+        Bandwidth::BW7_8kHz => 0x07,
+    }
+}
+
+fn bandwidth_from_hal(other: u8) -> Result<Bandwidth> {
+    Ok(match other {
+        0 => Bandwidth::Undefined,
+        0x01 => Bandwidth::BW500kHz,
+        0x02 => Bandwidth::BW250kHz,
+        0x03 => Bandwidth::BW125kHz,
+        0x04 => Bandwidth::BW62_5kHz,
+        0x05 => Bandwidth::BW31_2kHz,
+        0x06 => Bandwidth::BW15_6kHz,
+        0x07 => Bandwidth::BW7_8kHz,
+        invalid => {
+            log::error!("unable to convert {:?} to Bandwidth", invalid);
+            return Err(Error::Data);
+        }
+    })
+}
+
+impl From<&BoardConf> for llg::lgw_conf_board_s {
+    fn from(other: &BoardConf) -> Self {
+        llg::lgw_conf_board_s {
+            lorawan_public: other.lorawan_public,
+            clksrc: other.clksrc as u8,
+        }
+    }
+}
+
+impl From<&RxRFConf> for llg::lgw_conf_rxrf_s {
+    fn from(other: &RxRFConf) -> Self {
+        llg::lgw_conf_rxrf_s {
+            enable: other.enable,
+            freq_hz: other.freq,
+            rssi_offset: other.rssi_offset,
+            type_: other.type_ as u32,
+            tx_enable: other.tx_enable,
+            tx_notch_freq: other.tx_notch_freq,
+        }
+    }
+}
+
+impl TryFrom<&ChannelConf> for llg::lgw_conf_rxif_s {
+    type Error = Error;
+    fn try_from(other: &ChannelConf) -> Result<Self> {
+        Ok(match *other {
+            ChannelConf::Disable => llg::lgw_conf_rxif_s {
+                enable: false,
+                rf_chain: 0,
+                freq_hz: 0,
+                bandwidth: 0,
+                datarate: 0,
+                sync_word_size: 0,
+                sync_word: 0,
+            },
+            ChannelConf::Fixed {
+                radio,
+                freq,
+                bandwidth,
+                spreading,
+            } => llg::lgw_conf_rxif_s {
+                enable: true,
+                rf_chain: radio as u8,
+                freq_hz: freq,
+                bandwidth: bandwidth_to_hal(bandwidth),
+                datarate: spreading_to_hal(spreading)?,
+                sync_word_size: 0,
+                sync_word: 0,
+            },
+            ChannelConf::Multirate { radio, freq } => llg::lgw_conf_rxif_s {
+                enable: true,
+                rf_chain: radio as u8,
+                freq_hz: freq,
+                bandwidth: bandwidth_to_hal(Bandwidth::Undefined),
+                datarate: spreading_to_hal(Spreading::Undefined)?,
+                sync_word_size: 0,
+                sync_word: 0,
+            },
+            ChannelConf::FSK {
+                radio,
+                freq,
+                bandwidth,
+                datarate,
+                sync_word_size,
+                sync_word,
+            } => llg::lgw_conf_rxif_s {
+                enable: true,
+                rf_chain: radio as u8,
+                freq_hz: freq,
+                bandwidth: bandwidth_to_hal(bandwidth),
+                datarate,
+                sync_word_size,
+                sync_word,
+            },
+        })
+    }
+}
+
+impl From<&LBTConf> for llg::lgw_conf_lbt_s {
+    fn from(other: &LBTConf) -> Self {
+        let mut conf = llg::lgw_conf_lbt_s {
+            enable: other.enable,
+            rssi_target: other.rssi_target,
+            // The HAL wants a channel even to disable LBT, and
+            // ignores it then.
+            nb_channel: other.channels.len().max(1) as u8,
+            rssi_offset: other.rssi_offset,
+            ..Default::default()
+        };
+        for (dst, src) in conf.channels.iter_mut().zip(&other.channels) {
+            *dst = llg::lgw_conf_lbt_chan_s {
+                freq_hz: src.freq,
+                scan_time_us: src.scan_time_us,
+            };
+        }
+        conf
+    }
+}
+
+impl From<&TxGain> for llg::lgw_tx_gain_s {
+    fn from(other: &TxGain) -> Self {
+        llg::lgw_tx_gain_s {
+            dig_gain: other.dig_gain,
+            pa_gain: other.pa_gain,
+            dac_gain: other.dac_gain,
+            mix_gain: other.mix_gain,
+            rf_power: other.rf_power,
+        }
+    }
+}
+
+impl TryFrom<&llg::lgw_pkt_rx_s> for RxPacket {
+    type Error = Error;
+    fn try_from(other: &llg::lgw_pkt_rx_s) -> Result<Self> {
+        Ok(match other.modulation {
+            MOD_LORA => RxPacket::LoRa(RxPacketLoRa {
+                freq: other.freq_hz,
+                if_chain: other.if_chain,
+                crc_check: CRCCheck::try_from(u32::from(other.status))?,
+                timestamp: time::Duration::from_micros(u64::from(other.count_us)),
+                utc: None,
+                gps_time: None,
+                radio: Radio::try_from(u32::from(other.rf_chain))?,
+                bandwidth: bandwidth_from_hal(other.bandwidth)?,
+                spreading: spreading_from_hal(other.datarate)?,
+                coderate: Coderate::try_from(u32::from(other.coderate))?,
+                rssi: other.rssi,
+                snr: other.snr,
+                snr_min: other.snr_min,
+                snr_max: other.snr_max,
+                crc: other.crc,
+                payload: other.payload[..other.size as usize].to_vec(),
+            }),
+            MOD_FSK => RxPacket::FSK(RxPacketFSK {
+                freq: other.freq_hz,
+                if_chain: other.if_chain,
+                crc_check: CRCCheck::try_from(u32::from(other.status))?,
+                timestamp: time::Duration::from_micros(u64::from(other.count_us)),
+                radio: Radio::try_from(u32::from(other.rf_chain))?,
+                datarate: other.datarate,
+                rssi: other.rssi,
+                crc: other.crc,
+                payload: other.payload[..other.size as usize].to_vec(),
+            }),
+            invalid => {
+                log::error!("unable to convert {:?} to RxPacket", invalid);
+                return Err(Error::Data);
+            }
+        })
+    }
+}
+
+impl TryFrom<TxPacket> for llg::lgw_pkt_tx_s {
+    type Error = Error;
+    fn try_from(other: TxPacket) -> Result<Self> {
+        let mut payload = [0u8; 256];
+        let size = {
+            let src = match &other {
+                TxPacket::LoRa(pkt) => &pkt.payload,
+                TxPacket::FSK(pkt) => &pkt.payload,
+            };
+            if src.len() > payload.len() {
+                log::error!("attempt to send {} byte payload", src.len());
+                return Err(Error::Size);
+            }
+            payload[..src.len()].copy_from_slice(src);
+            src.len() as u16
+        };
+        Ok(match other {
+            TxPacket::LoRa(other) => {
+                let (mode, delay) = other.mode.into();
+                llg::lgw_pkt_tx_s {
+                    freq_hz: other.freq,
+                    tx_mode: mode,
+                    count_us: delay,
+                    rf_chain: other.radio as u8,
+                    rf_power: other.power,
+                    modulation: MOD_LORA,
+                    bandwidth: bandwidth_to_hal(other.bandwidth),
+                    datarate: spreading_to_hal(other.spreading)?,
+                    coderate: other.coderate as u8,
+                    invert_pol: other.invert_polarity,
+                    f_dev: 0,
+                    preamble: other.preamble.unwrap_or(0),
+                    no_crc: other.omit_crc,
+                    no_header: other.implicit_header,
+                    size,
+                    payload,
+                }
+            }
+            TxPacket::FSK(other) => {
+                let (mode, delay) = other.mode.into();
+                llg::lgw_pkt_tx_s {
+                    freq_hz: other.freq,
+                    tx_mode: mode,
+                    count_us: delay,
+                    rf_chain: other.radio as u8,
+                    rf_power: other.power,
+                    modulation: MOD_FSK,
+                    bandwidth: 0,
+                    datarate: other.datarate,
+                    coderate: Coderate::Undefined as u8,
+                    invert_pol: false,
+                    f_dev: other.deviation,
+                    preamble: other.preamble.unwrap_or(0),
+                    no_crc: other.omit_crc,
+                    no_header: other.fixed_len,
+                    size,
+                    payload,
+                }
+            }
+        })
+    }
+}
+
+pub(crate) fn config_board(conf: &BoardConf) -> Result {
+    unsafe { hal_call!(Config, lgw_board_setconf(conf.into()); "{:?}", conf) }?;
+    Ok(())
+}
+
+pub(crate) fn config_rx_rf(conf: &RxRFConf) -> Result {
+    unsafe {
+        hal_call!(Config, lgw_rxrf_setconf(conf.radio as u8, conf.into());
+                  "{:?} at {} Hz", conf.radio, conf.freq)
+    }?;
+    Ok(())
+}
+
+pub(crate) fn config_channel(chain: u8, conf: &ChannelConf) -> Result {
+    unsafe {
+        hal_call!(Config, lgw_rxif_setconf(chain, conf.try_into()?); "chain {}, {:?}", chain, conf)
+    }?;
+    Ok(())
+}
+
+/// The SX1301 has a single LUT, used by both radios, which is
+/// configured as `Radio::R0`'s.
+pub(crate) fn config_tx_gain(radio: Radio, gains: &[TxGain]) -> Result {
+    if let Radio::R1 = radio {
+        log::error!(
+            "SX1301 has one TX gain LUT, configure it for {:?}",
+            Radio::R0
+        );
+        return Err(Error::Unsupported);
+    }
+    let mut lut = llg::lgw_tx_gain_lut_s::default();
+    for (dst, src) in lut.lut.iter_mut().zip(gains) {
+        *dst = src.into();
+    }
+    lut.size = gains.len() as u8;
+    unsafe { hal_call!(Config, lgw_txgain_setconf(&mut lut); "{} gains", gains.len()) }?;
+    Ok(())
+}
+
+pub(crate) fn config_lbt(conf: &LBTConf) -> Result {
+    unsafe { hal_call!(Config, lgw_lbt_setconf(conf.into()); "{:?}", conf) }?;
+    Ok(())
+}
+
+pub(crate) fn start() -> Result {
+    unsafe { hal_call!(Spi, lgw_start()) }?;
+    Ok(())
+}
+
+pub(crate) fn stop() -> Result {
+    unsafe { hal_call!(Spi, lgw_stop()) }?;
+    Ok(())
+}
+
+/// The SX1301 has one TX path, whose status is reported for either
+/// radio.
+pub(crate) fn status(_radio: Radio, select: u8) -> Result<u8> {
+    let mut status = 0xFE;
+    unsafe { hal_call!(State, lgw_status(select, &mut status); "select {}", select) }?;
+    Ok(status)
+}
+
+pub(crate) fn receive() -> Result<Vec<RxPacket>> {
+    let mut tmp_buf: [llg::lgw_pkt_rx_s; 16] = [Default::default(); 16];
+    let len = unsafe {
+        hal_call!(
+            State,
+            lgw_receive(tmp_buf.len() as u8, tmp_buf.as_mut_ptr())
+        )
+    }?;
+    tmp_buf[..len].iter().map(RxPacket::try_from).collect()
+}
+
+pub(crate) fn send(packet: TxPacket) -> Result {
+    let (radio, freq, len) = (packet.radio(), packet.freq(), packet.payload().len());
+    // Other than before start, the HAL only refuses packets whose
+    // parameters the radio can't use.
+    match unsafe {
+        hal_call!(Config, lgw_send(packet.try_into()?);
+                  "{:?} at {} Hz, {} byte payload", radio, freq, len)
+    }? {
+        LGW_LBT_ISSUE => {
+            log::warn!("transmission denied by LBT");
+            Err(Error::LBT)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// `lgw_start` enables GPS latching, which freezes `LGW_TIMESTAMP` at
+/// the last PPS edge, so like the packet forwarder we briefly disable
+/// it to read the free-running counter.
+pub(crate) fn counter() -> Result<u32> {
+    let mut count_us = 0;
+    unsafe { hal_call!(State, lgw_reg_w(LGW_GPS_EN, 0)) }?;
+    let read = unsafe { hal_call!(State, lgw_reg_r(LGW_TIMESTAMP, &mut count_us)) };
+    unsafe { hal_call!(State, lgw_reg_w(LGW_GPS_EN, 1)) }?;
+    read?;
+    Ok(count_us as u32)
+}
+
+pub(crate) fn pps_counter() -> Result<u32> {
+    let mut count_us = 0;
+    unsafe { hal_call!(State, lgw_get_trigcnt(&mut count_us)) }?;
+    Ok(count_us)
+}
+
+pub(crate) fn abort_tx() -> Result {
+    unsafe { hal_call!(State, lgw_abort_tx()) }?;
+    Ok(())
+}
+
+/// The SX1301 has a single TX path shared by both radios, so it can't
+/// abort a transmission on one of them only.
+pub(crate) fn abort_tx_on(radio: Radio) -> Result {
+    log::error!("SX1301 can't abort TX on {:?} only", radio);
+    Err(Error::Unsupported)
+}
+
+pub(crate) fn temperature() -> Result<f32> {
+    log::error!("SX1301 boards have no temperature sensor");
+    Err(Error::Unsupported)
+}
+
+pub(crate) fn version_info() -> Result<VersionInfo> {
+    let info = unsafe { CStr::from_ptr(llg::lgw_version_info()) };
+    // Register reads fail until `lgw_connect` or `lgw_start` has
+    // opened the SPI link.
+    let read = |f: unsafe extern "C" fn(u16, *mut i32) -> i32, reg| {
+        let mut val = 0;
+        match unsafe { f(reg, &mut val) } {
+            0 => Some(val as u8),
+            _ => None,
+        }
+    };
+    Ok(VersionInfo {
+        hal: VersionInfo::parse_hal(&info.to_string_lossy()),
+        chip: Chip::SX1301,
+        chip_version: read(llg::lgw_reg_r, LGW_VERSION),
+        fpga_version: read(llg::lgw_fpga_reg_r, LGW_FPGA_VERSION)
+            .filter(|v| SUPPORTED_FPGA_VERSIONS.contains(v)),
+    })
+}
+
+/// The SX1301 HAL opens a compiled-in spidev path, so `_spidev_path`
+/// is ignored.
+pub(crate) fn connect(_spidev_path: &CStr) -> Result {
+    unsafe { hal_call!(Spi, lgw_connect(false, 0)) }?;
+    Ok(())
+}
@@ -0,0 +1,409 @@
+//! Conversions and HAL calls specific to the SX1302 `sx1302_hal`.
+
+use crate::{
+    error::{Error, Result},
+    types::{MOD_FSK, MOD_LORA},
+    Bandwidth, BoardConf, CRCCheck, ChannelConf, Chip, Coderate, LBTConf, Radio, RxPacket,
+    RxPacketFSK, RxPacketLoRa, RxRFConf, Spreading, TxGain, TxPacket, VersionInfo,
+};
+use libloragw_sx1302_sys as llg;
+use std::{
+    convert::{TryFrom, TryInto},
+    ffi::CStr,
+    os::raw::c_char,
+    time,
+};
+
+fn spreading_to_hal(spreading: Spreading) -> u32 {
+    match spreading {
+        Spreading::Undefined => 0,
+        Spreading::SF5 => 5,
+        Spreading::SF6 => 6,
+        Spreading::SF7 => 7,
+        Spreading::SF8 => 8,
+        Spreading::SF9 => 9,
+        Spreading::SF10 => 10,
+        Spreading::SF11 => 11,
+        Spreading::SF12 => 12,
+        Spreading::Multi => 0x7E,
+    }
+}
+
+fn spreading_from_hal(other: u32) -> Result<Spreading> {
+    Ok(match other {
+        0 => Spreading::Undefined,
+        5 => Spreading::SF5,
+        6 => Spreading::SF6,
+        7 => Spreading::SF7,
+        8 => Spreading::SF8,
+        9 => Spreading::SF9,
+        10 => Spreading::SF10,
+        11 => Spreading::SF11,
+        12 => Spreading::SF12,
+        0x7E => Spreading::Multi,
+        invalid => {
+            log::error!("unable to convert {:?} to Spreading", invalid);
+            return Err(Error::Data);
+        }
+    })
+}
+
+fn bandwidth_to_hal(bandwidth: Bandwidth) -> Result<u8> {
+    Ok(match bandwidth {
+        Bandwidth::Undefined => 0,
+        Bandwidth::BW125kHz => 4,
+        Bandwidth::BW250kHz => 5,
+        Bandwidth::BW500kHz => 6,
+        unsupported => {
+            log::error!("{:?} is not supported by the SX1302", unsupported);
+            return Err(Error::Unsupported);
+        }
+    })
+}
+
+fn bandwidth_from_hal(other: u8) -> Result<Bandwidth> {
+    Ok(match other {
+        0 => Bandwidth::Undefined,
+        4 => Bandwidth::BW125kHz,
+        5 => Bandwidth::BW250kHz,
+        6 => Bandwidth::BW500kHz,
+        invalid => {
+            log::error!("unable to convert {:?} to Bandwidth", invalid);
+            return Err(Error::Data);
+        }
+    })
+}
+
+impl From<&BoardConf> for llg::lgw_conf_board_s {
+    fn from(other: &BoardConf) -> Self {
+        llg::lgw_conf_board_s {
+            lorawan_public: other.lorawan_public,
+            clksrc: other.clksrc as u8,
+            full_duplex: false,
+            spidev_path: {
+                let mut path = [0; 64];
+                for (dst, src) in path
+                    .iter_mut()
+                    .zip(other.spidev_path.as_bytes_with_nul().iter())
+                {
+                    *dst = *src as c_char
+                }
+                path
+            },
+        }
+    }
+}
+
+impl From<&RxRFConf> for llg::lgw_conf_rxrf_s {
+    fn from(other: &RxRFConf) -> Self {
+        llg::lgw_conf_rxrf_s {
+            enable: other.enable,
+            freq_hz: other.freq,
+            rssi_offset: other.rssi_offset,
+            type_: other.type_ as u32,
+            tx_enable: other.tx_enable,
+            // TODO: expose RSSI temperature compensation coefficients.
+            ..Default::default()
+        }
+    }
+}
+
+impl TryFrom<&ChannelConf> for llg::lgw_conf_rxif_s {
+    type Error = Error;
+    fn try_from(other: &ChannelConf) -> Result<Self> {
+        Ok(match *other {
+            ChannelConf::Disable => llg::lgw_conf_rxif_s {
+                enable: false,
+                ..Default::default()
+            },
+            ChannelConf::Fixed {
+                radio,
+                freq,
+                bandwidth,
+                spreading,
+            } => llg::lgw_conf_rxif_s {
+                enable: true,
+                rf_chain: radio as u8,
+                freq_hz: freq,
+                bandwidth: bandwidth_to_hal(bandwidth)?,
+                datarate: spreading_to_hal(spreading),
+                ..Default::default()
+            },
+            ChannelConf::Multirate { radio, freq } => llg::lgw_conf_rxif_s {
+                enable: true,
+                rf_chain: radio as u8,
+                freq_hz: freq,
+                bandwidth: bandwidth_to_hal(Bandwidth::Undefined)?,
+                datarate: spreading_to_hal(Spreading::Undefined),
+                ..Default::default()
+            },
+            ChannelConf::FSK {
+                radio,
+                freq,
+                bandwidth,
+                datarate,
+                sync_word_size,
+                sync_word,
+            } => llg::lgw_conf_rxif_s {
+                enable: true,
+                rf_chain: radio as u8,
+                freq_hz: freq,
+                bandwidth: bandwidth_to_hal(bandwidth)?,
+                datarate,
+                sync_word_size,
+                sync_word,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+impl From<&TxGain> for llg::lgw_tx_gain_s {
+    fn from(other: &TxGain) -> Self {
+        llg::lgw_tx_gain_s {
+            rf_power: other.rf_power,
+            dig_gain: other.dig_gain,
+            pa_gain: other.pa_gain,
+            dac_gain: other.dac_gain,
+            mix_gain: other.mix_gain,
+            offset_i: other.offset_i,
+            offset_q: other.offset_q,
+            pwr_idx: other.pwr_id,
+        }
+    }
+}
+
+impl TryFrom<&llg::lgw_pkt_rx_s> for RxPacket {
+    type Error = Error;
+    fn try_from(other: &llg::lgw_pkt_rx_s) -> Result<Self> {
+        Ok(match other.modulation {
+            MOD_LORA => RxPacket::LoRa(RxPacketLoRa {
+                freq: other.freq_hz,
+                if_chain: other.if_chain,
+                crc_check: CRCCheck::try_from(u32::from(other.status))?,
+                timestamp: time::Duration::from_micros(u64::from(other.count_us)),
+                utc: None,
+                gps_time: None,
+                radio: Radio::try_from(u32::from(other.rf_chain))?,
+                bandwidth: bandwidth_from_hal(other.bandwidth)?,
+                spreading: spreading_from_hal(other.datarate)?,
+                coderate: Coderate::try_from(u32::from(other.coderate))?,
+                rssi: other.rssis,
+                snr: other.snr,
+                snr_min: other.snr_min,
+                snr_max: other.snr_max,
+                crc: other.crc,
+                payload: other.payload[..other.size as usize].to_vec(),
+            }),
+            MOD_FSK => RxPacket::FSK(RxPacketFSK {
+                freq: other.freq_hz,
+                if_chain: other.if_chain,
+                crc_check: CRCCheck::try_from(u32::from(other.status))?,
+                timestamp: time::Duration::from_micros(u64::from(other.count_us)),
+                radio: Radio::try_from(u32::from(other.rf_chain))?,
+                datarate: other.datarate,
+                rssi: other.rssis,
+                crc: other.crc,
+                payload: other.payload[..other.size as usize].to_vec(),
+            }),
+            invalid => {
+                log::error!("unable to convert {:?} to RxPacket", invalid);
+                return Err(Error::Data);
+            }
+        })
+    }
+}
+
+impl TryFrom<TxPacket> for llg::lgw_pkt_tx_s {
+    type Error = Error;
+    fn try_from(other: TxPacket) -> Result<Self> {
+        let mut payload = [0u8; 256];
+        let size = {
+            let src = match &other {
+                TxPacket::LoRa(pkt) => &pkt.payload,
+                TxPacket::FSK(pkt) => &pkt.payload,
+            };
+            if src.len() > payload.len() {
+                log::error!("attempt to send {} byte payload", src.len());
+                return Err(Error::Size);
+            }
+            payload[..src.len()].copy_from_slice(src);
+            src.len() as u16
+        };
+        Ok(match other {
+            TxPacket::LoRa(other) => {
+                let (mode, delay) = other.mode.into();
+                llg::lgw_pkt_tx_s {
+                    freq_hz: other.freq,
+                    tx_mode: mode,
+                    count_us: delay,
+                    rf_chain: other.radio as u8,
+                    rf_power: other.power,
+                    modulation: MOD_LORA,
+                    freq_offset: 0,
+                    bandwidth: bandwidth_to_hal(other.bandwidth)?,
+                    datarate: spreading_to_hal(other.spreading),
+                    coderate: other.coderate as u8,
+                    invert_pol: other.invert_polarity,
+                    f_dev: 0,
+                    preamble: other.preamble.unwrap_or(0),
+                    no_crc: other.omit_crc,
+                    no_header: other.implicit_header,
+                    size,
+                    payload,
+                }
+            }
+            TxPacket::FSK(other) => {
+                let (mode, delay) = other.mode.into();
+                llg::lgw_pkt_tx_s {
+                    freq_hz: other.freq,
+                    tx_mode: mode,
+                    count_us: delay,
+                    rf_chain: other.radio as u8,
+                    rf_power: other.power,
+                    modulation: MOD_FSK,
+                    freq_offset: 0,
+                    bandwidth: 0,
+                    datarate: other.datarate,
+                    coderate: Coderate::Undefined as u8,
+                    invert_pol: false,
+                    f_dev: other.deviation,
+                    preamble: other.preamble.unwrap_or(0),
+                    no_crc: other.omit_crc,
+                    no_header: other.fixed_len,
+                    size,
+                    payload,
+                }
+            }
+        })
+    }
+}
+
+pub(crate) fn config_board(conf: &BoardConf) -> Result {
+    unsafe { hal_call!(Config, lgw_board_setconf(&mut conf.into()); "{:?}", conf) }?;
+    Ok(())
+}
+
+pub(crate) fn config_rx_rf(conf: &RxRFConf) -> Result {
+    unsafe {
+        hal_call!(Config, lgw_rxrf_setconf(conf.radio as u8, &mut conf.into());
+                  "{:?} at {} Hz", conf.radio, conf.freq)
+    }?;
+    Ok(())
+}
+
+pub(crate) fn config_channel(chain: u8, conf: &ChannelConf) -> Result {
+    unsafe {
+        hal_call!(Config, lgw_rxif_setconf(chain, &mut conf.try_into()?);
+                  "chain {}, {:?}", chain, conf)
+    }?;
+    Ok(())
+}
+
+pub(crate) fn config_tx_gain(radio: Radio, gains: &[TxGain]) -> Result {
+    let mut lut = llg::lgw_tx_gain_lut_s::default();
+    for (dst, src) in lut.lut.iter_mut().zip(gains) {
+        *dst = src.into();
+    }
+    lut.size = gains.len() as u8;
+    unsafe {
+        hal_call!(Config, lgw_txgain_setconf(radio as u8, &mut lut);
+                  "{:?}, {} gains", radio, gains.len())
+    }?;
+    Ok(())
+}
+
+pub(crate) fn config_lbt(conf: &LBTConf) -> Result {
+    if conf.enable {
+        log::error!("LBT is not supported by the SX1302 HAL");
+        return Err(Error::Unsupported);
+    }
+    Ok(())
+}
+
+pub(crate) fn start() -> Result {
+    unsafe { hal_call!(Spi, lgw_start()) }?;
+    Ok(())
+}
+
+pub(crate) fn stop() -> Result {
+    unsafe { hal_call!(Spi, lgw_stop()) }?;
+    Ok(())
+}
+
+pub(crate) fn status(radio: Radio, select: u8) -> Result<u8> {
+    let mut status = 0xFE;
+    unsafe {
+        hal_call!(State, lgw_status(radio as u8, select, &mut status);
+                  "{:?}, select {}", radio, select)
+    }?;
+    Ok(status)
+}
+
+pub(crate) fn receive() -> Result<Vec<RxPacket>> {
+    let mut tmp_buf: [llg::lgw_pkt_rx_s; 16] = [Default::default(); 16];
+    let len = unsafe {
+        hal_call!(
+            State,
+            lgw_receive(tmp_buf.len() as u8, tmp_buf.as_mut_ptr())
+        )
+    }?;
+    tmp_buf[..len].iter().map(RxPacket::try_from).collect()
+}
+
+pub(crate) fn send(packet: TxPacket) -> Result {
+    let (radio, freq, len) = (packet.radio(), packet.freq(), packet.payload().len());
+    // Other than before start, the HAL only refuses packets whose
+    // parameters the radio can't use.
+    unsafe {
+        hal_call!(Config, lgw_send(&mut packet.try_into()?);
+                  "{:?} at {} Hz, {} byte payload", radio, freq, len)
+    }?;
+    Ok(())
+}
+
+pub(crate) fn counter() -> Result<u32> {
+    let mut count_us = 0;
+    unsafe { hal_call!(State, lgw_get_instcnt(&mut count_us)) }?;
+    Ok(count_us)
+}
+
+pub(crate) fn pps_counter() -> Result<u32> {
+    let mut count_us = 0;
+    unsafe { hal_call!(State, lgw_get_trigcnt(&mut count_us)) }?;
+    Ok(count_us)
+}
+
+/// Reads the board's STTS751 temperature sensor, over I2C.
+pub(crate) fn temperature() -> Result<f32> {
+    let mut temperature = 0.0;
+    unsafe { hal_call!(State, lgw_get_temperature(&mut temperature)) }?;
+    Ok(temperature)
+}
+
+pub(crate) fn abort_tx() -> Result {
+    abort_tx_on(Radio::R0)?;
+    abort_tx_on(Radio::R1)
+}
+
+pub(crate) fn abort_tx_on(radio: Radio) -> Result {
+    unsafe { hal_call!(State, lgw_abort_tx(radio as u8); "{:?}", radio) }?;
+    Ok(())
+}
+
+/// The SX1302 has no FPGA, and its HAL doesn't expose the chip
+/// version, so only the HAL version is reported.
+pub(crate) fn version_info() -> Result<VersionInfo> {
+    let info = unsafe { CStr::from_ptr(llg::lgw_version_info()) };
+    Ok(VersionInfo {
+        hal: VersionInfo::parse_hal(&info.to_string_lossy()),
+        chip: Chip::SX1302,
+        chip_version: None,
+        fpga_version: None,
+    })
+}
+
+pub(crate) fn connect(spidev_path: &CStr) -> Result {
+    unsafe { hal_call!(Spi, lgw_connect(spidev_path.as_ptr()); "{:?}", spidev_path) }?;
+    Ok(())
+}
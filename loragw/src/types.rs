@@ -1,8 +1,157 @@
-use crate::{error, llg};
-use std::{convert::TryFrom, fmt, time};
+use crate::error;
+use std::{convert::TryFrom, fmt, str::FromStr, time};
+
+pub(crate) const MOD_LORA: u8 = 0x10;
+pub(crate) const MOD_FSK: u8 = 0x20;
+
+/// Concentrator chip families supported by this crate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Chip {
+    /// Semtech SX1301.
+    SX1301,
+    /// Semtech SX1302.
+    SX1302,
+}
+
+impl Chip {
+    /// Returns `true` if support for this chip was compiled in.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Chip::SX1301 => cfg!(feature = "sx1301"),
+            Chip::SX1302 => cfg!(feature = "sx1302"),
+        }
+    }
+
+    /// Returns the IF chain of the single spreading factor ("LoRa
+    /// standard" or "service") channel, which follows the multirate
+    /// chains.
+    pub fn lora_std_chain(self) -> u8 {
+        match self {
+            Chip::SX1301 => 8,
+            Chip::SX1302 => 8,
+        }
+    }
+
+    /// Returns the IF chain of the FSK channel.
+    pub fn fsk_chain(self) -> u8 {
+        match self {
+            Chip::SX1301 => 9,
+            Chip::SX1302 => 9,
+        }
+    }
+
+    /// Returns the width, in Hz, of the band centered on a radio's
+    /// frequency which IF channels of `bandwidth` must fall within, as
+    /// the HAL checks it.
+    pub fn rx_bandwidth(self, bandwidth: Bandwidth) -> u32 {
+        match (self, bandwidth) {
+            (Chip::SX1301, Bandwidth::BW250kHz) => 1_000_000,
+            (Chip::SX1301, Bandwidth::BW500kHz) => 1_100_000,
+            (Chip::SX1301, _) => 925_000,
+            (Chip::SX1302, _) => 1_600_000,
+        }
+    }
+
+    /// Returns `true` if both radios share a single TX path, so only
+    /// one of them can transmit at a time.
+    pub fn shared_tx_path(self) -> bool {
+        match self {
+            Chip::SX1301 => true,
+            Chip::SX1302 => false,
+        }
+    }
+
+    /// Returns the TX gain LUT the HAL uses for a radio until one is
+    /// configured.
+    pub fn default_tx_gains(self) -> Vec<TxGain> {
+        let gain = |rf_power, pa_gain, mix_gain| TxGain {
+            rf_power,
+            pa_gain,
+            dac_gain: 3,
+            mix_gain,
+            ..Default::default()
+        };
+        match self {
+            Chip::SX1301 => vec![gain(14, 2, 10), gain(27, 3, 14)],
+            Chip::SX1302 => vec![gain(14, 2, 10)],
+        }
+    }
+
+    /// Returns `true` if this chip can receive and transmit with
+    /// `spreading`.
+    pub fn supports_spreading(self, spreading: Spreading) -> bool {
+        match spreading {
+            Spreading::SF5 | Spreading::SF6 => self == Chip::SX1302,
+            _ => true,
+        }
+    }
+}
+
+impl Default for Chip {
+    /// Returns the first chip support was compiled in for, preferring
+    /// SX1301.
+    fn default() -> Self {
+        if cfg!(feature = "sx1301") {
+            Chip::SX1301
+        } else {
+            Chip::SX1302
+        }
+    }
+}
+
+impl FromStr for Chip {
+    type Err = error::Error;
+    fn from_str(s: &str) -> Result<Self, error::Error> {
+        Ok(match s {
+            "sx1301" | "SX1301" => Chip::SX1301,
+            "sx1302" | "SX1302" => Chip::SX1302,
+            invalid => {
+                log::error!("unable to convert {:?} to Chip", invalid);
+                return Err(error::Error::Data);
+            }
+        })
+    }
+}
+
+impl fmt::Display for Chip {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Chip::SX1301 => "sx1301",
+                Chip::SX1302 => "sx1302",
+            }
+        )
+    }
+}
 
-const MOD_LORA: u8 = 0x10;
-const MOD_FSK: u8 = 0x20;
+/// HAL and hardware versions of a concentrator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionInfo {
+    /// Version of the linked HAL library, e.g. "5.0.1".
+    pub hal: String,
+    /// Chip family the HAL drives.
+    pub chip: Chip,
+    /// Silicon version read from the chip, if connected.
+    pub chip_version: Option<u8>,
+    /// Version of the board's FPGA, if it has one and is connected.
+    pub fpga_version: Option<u8>,
+}
+
+impl VersionInfo {
+    /// Extracts the version from an `lgw_version_info` string of the
+    /// form "Version: 5.0.1;".
+    pub(crate) fn parse_hal(info: &str) -> String {
+        info.split(';')
+            .map(str::trim)
+            .find(|field| field.starts_with("Version:"))
+            .map(|field| &field["Version:".len()..])
+            .unwrap_or(info)
+            .trim()
+            .to_string()
+    }
+}
 
 /// Radio types that can be found on the LoRa concentrator.
 #[derive(Debug, Clone, Copy)]
@@ -35,146 +184,44 @@ impl TryFrom<&str> for RadioType {
 }
 
 /// Spreading factor.
-#[cfg(feature = "sx1301")]
-#[derive(Debug, Clone, Copy)]
+///
+/// `SF5` and `SF6` are only supported by the SX1302.
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[allow(missing_docs)]
 pub enum Spreading {
-    Undefined = 0x00,
-    SF7 = 0x02,
-    SF8 = 0x04,
-    SF9 = 0x08,
-    SF10 = 0x10,
-    SF11 = 0x20,
-    SF12 = 0x40,
-    Multi = 0x7E,
-}
-
-/// Spreading factor.
-#[cfg(feature = "sx1302")]
-#[derive(Debug, Clone, Copy)]
-#[allow(missing_docs)]
-pub enum Spreading {
-    Undefined = 0,
-    SF5 = 5,
-    SF6 = 6,
-    SF7 = 7,
-    SF8 = 8,
-    SF9 = 9,
-    SF10 = 10,
-    SF11 = 11,
-    SF12 = 12,
-    Multi = 0x7E,
-}
-
-impl TryFrom<u32> for Spreading {
-    type Error = error::Error;
-
-    #[cfg(feature = "sx1301")]
-    fn try_from(other: u32) -> Result<Self, error::Error> {
-        Ok(match other {
-            0x00 => Spreading::Undefined,
-            0x02 => Spreading::SF7,
-            0x04 => Spreading::SF8,
-            0x08 => Spreading::SF9,
-            0x10 => Spreading::SF10,
-            0x20 => Spreading::SF11,
-            0x40 => Spreading::SF12,
-            0x7E => Spreading::Multi,
-            _ => return Err(error::Error::Data),
-        })
-    }
-
-    #[cfg(feature = "sx1302")]
-    fn try_from(other: u32) -> Result<Self, error::Error> {
-        Ok(match other {
-            0 => Spreading::Undefined,
-            5 => Spreading::SF5,
-            6 => Spreading::SF6,
-            7 => Spreading::SF7,
-            8 => Spreading::SF8,
-            9 => Spreading::SF9,
-            10 => Spreading::SF10,
-            11 => Spreading::SF11,
-            12 => Spreading::SF12,
-            0x7E => Spreading::Multi,
-            invalid => {
-                log::error!("unable to convert {:?} to Spreading", invalid);
-                return Err(error::Error::Data);
-            }
-        })
-    }
+    Undefined,
+    SF5,
+    SF6,
+    SF7,
+    SF8,
+    SF9,
+    SF10,
+    SF11,
+    SF12,
+    Multi,
 }
 
 /// Configured receive bandwidth.
-#[cfg(feature = "sx1301")]
-#[derive(Debug, Clone, Copy)]
+///
+/// Bandwidths narrower than 125 kHz are only supported by the SX1301.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Bandwidth {
     /// Auto bandwidth.
-    Undefined = 0,
+    Undefined,
     /// 500 kHz.
-    BW500kHz = 0x01,
+    BW500kHz,
     /// 250 kHz.
-    BW250kHz = 0x02,
+    BW250kHz,
     /// 125 kHz.
-    BW125kHz = 0x03,
+    BW125kHz,
     /// 62.5 kHz
-    BW62_5kHz = 0x04,
+    BW62_5kHz,
     /// 31.2 kHz.
-    BW31_2kHz = 0x05,
+    BW31_2kHz,
     /// 15.6 kHz.
-    BW15_6kHz = 0x06,
+    BW15_6kHz,
     /// 7.8 kHz.
-    BW7_8kHz = 0x07,
-}
-
-/// Configured receive bandwidth.
-#[cfg(feature = "sx1302")]
-#[derive(Debug, Clone, Copy)]
-pub enum Bandwidth {
-    /// Auto bandwidth.
-    Undefined = 0,
-    /// 125 kHz.
-    BW125kHz = 4,
-    /// 250 kHz.
-    BW250kHz = 5,
-    /// 500 kHz.
-    BW500kHz = 6,
-}
-
-impl TryFrom<u32> for Bandwidth {
-    type Error = error::Error;
-
-    #[cfg(feature = "sx1301")]
-    fn try_from(other: u32) -> Result<Self, error::Error> {
-        Ok(match other {
-            0 => Bandwidth::Undefined,
-            0x01 => Bandwidth::BW500kHz,
-            0x02 => Bandwidth::BW250kHz,
-            0x03 => Bandwidth::BW125kHz,
-            0x04 => Bandwidth::BW62_5kHz,
-            0x05 => Bandwidth::BW31_2kHz,
-            0x06 => Bandwidth::BW15_6kHz,
-            0x07 => Bandwidth::BW7_8kHz,
-            invalid => {
-                log::error!("unable to convert {:?} to Bandwidth", invalid);
-                return Err(error::Error::Data);
-            }
-        })
-    }
-
-    #[cfg(feature = "sx1302")]
-    fn try_from(other: u32) -> Result<Self, error::Error> {
-        Ok(match other {
-            0 => Bandwidth::Undefined,
-            4 => Bandwidth::BW125kHz,
-            5 => Bandwidth::BW250kHz,
-            6 => Bandwidth::BW500kHz,
-            invalid => {
-                log::error!("unable to convert {:?} to Bandwidth", invalid);
-                return Err(error::Error::Data);
-            }
-        })
-    }
+    BW7_8kHz,
 }
 
 /// Configured error correction code rate.
@@ -257,29 +304,6 @@ pub struct BoardConf {
     pub spidev_path: ::std::ffi::CString,
 }
 
-impl From<&BoardConf> for llg::lgw_conf_board_s {
-    fn from(other: &BoardConf) -> Self {
-        llg::lgw_conf_board_s {
-            lorawan_public: other.lorawan_public,
-            clksrc: other.clksrc as u8,
-            #[cfg(feature = "sx1302")]
-            full_duplex: false,
-            #[cfg(feature = "sx1302")]
-            spidev_path: {
-                let mut path = [0; 64];
-                // let other_path = other.spidev_path.as_bytes_with_nul();
-                for (dst, src) in path
-                    .iter_mut()
-                    .zip(other.spidev_path.as_bytes_with_nul().iter())
-                {
-                    *dst = *src as ::std::os::raw::c_char
-                }
-                path
-            },
-        }
-    }
-}
-
 /// Configuration structure for LBT channels.
 #[derive(Debug, Clone)]
 pub struct LBTChanConf {
@@ -296,12 +320,12 @@ pub struct LBTConf {
     pub enable: bool,
     /// RSSI threshold for deciding if channel is currently busy.
     pub rssi_target: i8,
-    /// Number of LBT chanels.
-    pub nb_channel: u8,
-    /// Channel map.
-    pub channels: [LBTChanConf; 8],
     /// RSSI offset to be applied to SX127x RSSI values.
     pub rssi_offset: i8,
+    /// Channels to listen on (1 to 8 when enabled).
+    ///
+    /// Transmissions are only allowed on these channels.
+    pub channels: Vec<LBTChanConf>,
 }
 
 /// RF chain configuration.
@@ -323,20 +347,6 @@ pub struct RxRFConf {
     pub tx_notch_freq: u32,
 }
 
-impl From<&RxRFConf> for llg::lgw_conf_rxrf_s {
-    fn from(other: &RxRFConf) -> Self {
-        log::warn!("add missing fields");
-        llg::lgw_conf_rxrf_s {
-            enable: other.enable,
-            freq_hz: other.freq,
-            rssi_offset: other.rssi_offset,
-            type_: other.type_ as u32,
-            tx_enable: other.tx_enable,
-            ..Default::default()
-        }
-    }
-}
-
 /// Modem and IF configuration.
 #[derive(Debug, Clone)]
 pub enum ChannelConf {
@@ -380,66 +390,6 @@ pub enum ChannelConf {
     },
 }
 
-#[allow(clippy::needless_update)]
-impl From<&ChannelConf> for llg::lgw_conf_rxif_s {
-    fn from(other: &ChannelConf) -> Self {
-        match other {
-            ChannelConf::Disable => llg::lgw_conf_rxif_s {
-                enable: false,
-                rf_chain: 0,
-                freq_hz: 0,
-                bandwidth: 0,
-                datarate: 0,
-                sync_word_size: 0,
-                sync_word: 0,
-                ..Default::default()
-            },
-            &ChannelConf::Fixed {
-                radio,
-                freq,
-                bandwidth,
-                spreading,
-            } => llg::lgw_conf_rxif_s {
-                enable: true,
-                rf_chain: radio as u8,
-                freq_hz: freq,
-                bandwidth: bandwidth as u8,
-                datarate: spreading as u32,
-                sync_word_size: 0,
-                sync_word: 0,
-                ..Default::default()
-            },
-            &ChannelConf::Multirate { radio, freq } => llg::lgw_conf_rxif_s {
-                enable: true,
-                rf_chain: radio as u8,
-                freq_hz: freq,
-                bandwidth: Bandwidth::Undefined as u8,
-                datarate: Spreading::Undefined as u32,
-                sync_word_size: 0,
-                sync_word: 0,
-                ..Default::default()
-            },
-            &ChannelConf::FSK {
-                radio,
-                freq,
-                bandwidth,
-                datarate,
-                sync_word_size,
-                sync_word,
-            } => llg::lgw_conf_rxif_s {
-                enable: true,
-                rf_chain: radio as u8,
-                freq_hz: freq,
-                bandwidth: bandwidth as u8,
-                datarate,
-                sync_word_size,
-                sync_word,
-                ..Default::default()
-            },
-        }
-    }
-}
-
 /// Status of CRC check returned with received packets.
 #[derive(Debug, Clone, Copy)]
 pub enum CRCCheck {
@@ -467,7 +417,7 @@ impl TryFrom<u32> for CRCCheck {
 }
 
 /// A received LoRa-modulated packet.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RxPacketLoRa {
     /// Center frequency of the channel this packet was received on.
     pub freq: u32,
@@ -476,7 +426,21 @@ pub struct RxPacketLoRa {
     /// Status of CRC check.
     pub crc_check: CRCCheck,
     /// 1uS-resolution timestamp derived from concentrator's internal counter.
+    ///
+    /// The counter is extended to 64 bits, so timestamps keep
+    /// increasing when its 32 bits wrap.
     pub timestamp: time::Duration,
+    /// UTC time this packet was received at.
+    ///
+    /// Only available once a `gps::Gps` has a time reference, see
+    /// `gps::Gps::stamp`.
+    pub utc: Option<time::SystemTime>,
+    /// Time this packet was received at, since the GPS epoch
+    /// (1980-01-06T00:00:00Z).
+    ///
+    /// Only available with receivers which report GPS time (u-blox
+    /// UBX NAV-TIMEGPS), see `gps::Gps::stamp`.
+    pub gps_time: Option<time::Duration>,
     /// RF chain this packet was received on.
     pub radio: Radio,
     /// Modulation bandwidth.
@@ -502,7 +466,7 @@ pub struct RxPacketLoRa {
 }
 
 /// A received FSK-modulated packet.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RxPacketFSK {
     /// Center frequency of the channel this packet was received on.
     pub freq: u32,
@@ -511,6 +475,9 @@ pub struct RxPacketFSK {
     /// Status of CRC check.
     pub crc_check: CRCCheck,
     /// 1uS-resolution timestamp derived from concentrator's internal counter.
+    ///
+    /// The counter is extended to 64 bits, so timestamps keep
+    /// increasing when its 32 bits wrap.
     pub timestamp: time::Duration,
     /// RF chain this packet was received on.
     pub radio: Radio,
@@ -527,7 +494,7 @@ pub struct RxPacketFSK {
 }
 
 /// A received packet.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RxPacket {
     /// This packet was transmitted using FSK modulation.
     FSK(RxPacketFSK),
@@ -535,48 +502,12 @@ pub enum RxPacket {
     LoRa(RxPacketLoRa),
 }
 
-impl TryFrom<&llg::lgw_pkt_rx_s> for RxPacket {
-    type Error = error::Error;
-    fn try_from(other: &llg::lgw_pkt_rx_s) -> Result<Self, Self::Error> {
-        Ok(match other.modulation {
-            MOD_LORA => RxPacket::LoRa(RxPacketLoRa {
-                freq: other.freq_hz,
-                if_chain: other.if_chain,
-                crc_check: CRCCheck::try_from(u32::from(other.status))?,
-                timestamp: time::Duration::from_micros(u64::from(other.count_us)),
-                radio: Radio::try_from(u32::from(other.rf_chain))?,
-                bandwidth: Bandwidth::try_from(u32::from(other.bandwidth))?,
-                spreading: Spreading::try_from(other.datarate)?,
-                coderate: Coderate::try_from(u32::from(other.coderate))?,
-                #[cfg(feature = "sx1301")]
-                rssi: other.rssi,
-                #[cfg(feature = "sx1302")]
-                rssi: other.rssis,
-                snr: other.snr,
-                snr_min: other.snr_min,
-                snr_max: other.snr_max,
-                crc: other.crc,
-                payload: other.payload[..other.size as usize].to_vec(),
-            }),
-            MOD_FSK => RxPacket::FSK(RxPacketFSK {
-                freq: other.freq_hz,
-                if_chain: other.if_chain,
-                crc_check: CRCCheck::try_from(u32::from(other.status))?,
-                timestamp: time::Duration::from_micros(u64::from(other.count_us)),
-                radio: Radio::try_from(u32::from(other.rf_chain))?,
-                datarate: other.datarate,
-                #[cfg(feature = "sx1301")]
-                rssi: other.rssi,
-                #[cfg(feature = "sx1302")]
-                rssi: other.rssis,
-                crc: other.crc,
-                payload: other.payload[..other.size as usize].to_vec(),
-            }),
-            invalid => {
-                log::error!("unable to convert {:?} to RxPacket", invalid);
-                return Err(error::Error::Data);
-            }
-        })
+impl RxPacket {
+    pub(crate) fn timestamp_mut(&mut self) -> &mut time::Duration {
+        match self {
+            RxPacket::LoRa(pkt) => &mut pkt.timestamp,
+            RxPacket::FSK(pkt) => &mut pkt.timestamp,
+        }
     }
 }
 
@@ -589,11 +520,16 @@ pub enum TxMode {
     /// analog circuitry settling.
     Immediate,
     /// Send when concentrator's internal counter equals the time specified.
+    ///
+    /// Like `RxPacket` timestamps, the counter is extended to 64 bits.
+    /// Times less than 2^31 µs (about 35.8 minutes) ahead of the
+    /// counter are accepted, see `Concentrator::transmit`.
     Timestamp(time::Duration),
     /// Send at specified duration after the next GPS pulse-per-second transition.
     PPS(time::Duration),
 }
 
+/// Timestamps must already be within the counter's 32 bits.
 impl From<TxMode> for (u8, u32) {
     fn from(other: TxMode) -> (u8, u32) {
         use crate::TxMode::*;
@@ -614,6 +550,40 @@ pub enum TxPacket {
     FSK(TxPacketFSK),
 }
 
+impl TxPacket {
+    /// Returns the radio this packet is to be transmitted on.
+    pub fn radio(&self) -> Radio {
+        match self {
+            TxPacket::LoRa(pkt) => pkt.radio,
+            TxPacket::FSK(pkt) => pkt.radio,
+        }
+    }
+
+    /// Returns the center frequency this packet is to be transmitted
+    /// on.
+    pub fn freq(&self) -> u32 {
+        match self {
+            TxPacket::LoRa(pkt) => pkt.freq,
+            TxPacket::FSK(pkt) => pkt.freq,
+        }
+    }
+
+    /// Returns this packet's payload.
+    pub fn payload(&self) -> &[u8] {
+        match self {
+            TxPacket::LoRa(pkt) => &pkt.payload,
+            TxPacket::FSK(pkt) => &pkt.payload,
+        }
+    }
+
+    pub(crate) fn mode_mut(&mut self) -> &mut TxMode {
+        match self {
+            TxPacket::LoRa(pkt) => &mut pkt.mode,
+            TxPacket::FSK(pkt) => &mut pkt.mode,
+        }
+    }
+}
+
 /// A transmittable LoRa packet.
 #[derive(Debug, Clone)]
 pub struct TxPacketLoRa {
@@ -672,120 +642,28 @@ pub struct TxPacketFSK {
     pub payload: Vec<u8>,
 }
 
-impl TryFrom<TxPacket> for llg::lgw_pkt_tx_s {
-    type Error = error::Error;
-
-    #[cfg_attr(feature = "sx1301", allow(clippy::needless_update))]
-    fn try_from(other: TxPacket) -> Result<Self, error::Error> {
-        match other {
-            TxPacket::LoRa(other) => {
-                if other.payload.len() > 256 {
-                    log::error!("attempt to send {} byte payload", other.payload.len());
-                    Err(error::Error::Size)
-                } else {
-                    let (mode, delay) = other.mode.into();
-                    log::warn!("add missing fields");
-                    Ok(llg::lgw_pkt_tx_s {
-                        freq_hz: other.freq,
-                        tx_mode: mode,
-                        count_us: delay,
-                        rf_chain: other.radio as u8,
-                        rf_power: other.power,
-                        modulation: MOD_LORA,
-                        bandwidth: other.bandwidth as u8,
-                        datarate: other.spreading as u32,
-                        coderate: other.coderate as u8,
-                        invert_pol: other.invert_polarity,
-                        f_dev: 0,
-                        preamble: other.preamble.unwrap_or(0),
-
-                        no_crc: other.omit_crc,
-                        no_header: other.implicit_header,
-                        size: other.payload.len() as u16,
-                        payload: {
-                            let mut buf: [u8; 256] = [0u8; 256];
-                            buf[..other.payload.len()].copy_from_slice(other.payload.as_ref());
-                            buf
-                        },
-                        ..Default::default()
-                    })
-                }
-            }
-            TxPacket::FSK(other) => {
-                if other.payload.len() > 256 {
-                    log::error!("attempt to send {} byte payload", other.payload.len());
-                    Err(error::Error::Size)
-                } else {
-                    let (mode, delay) = other.mode.into();
-                    log::warn!("add missing fields");
-                    Ok(llg::lgw_pkt_tx_s {
-                        freq_hz: other.freq,
-                        tx_mode: mode,
-                        count_us: delay,
-                        rf_chain: other.radio as u8,
-                        rf_power: other.power,
-                        modulation: MOD_FSK,
-                        bandwidth: 0,
-                        datarate: other.datarate,
-                        coderate: Coderate::Undefined as u8,
-                        invert_pol: false,
-                        f_dev: other.deviation,
-                        preamble: other.preamble.unwrap_or(0),
-                        no_crc: other.omit_crc,
-                        no_header: other.fixed_len,
-                        size: other.payload.len() as u16,
-                        payload: {
-                            let mut buf: [u8; 256] = [0u8; 256];
-                            buf[..other.payload.len()].copy_from_slice(other.payload.as_ref());
-                            buf
-                        },
-                        ..Default::default()
-                    })
-                }
-            }
-        }
-    }
-}
-
 /// Structure containing all gains of Tx chain.
-#[repr(C)]
 #[derive(Debug, Clone, Default)]
 pub struct TxGain {
     /// Measured TX power at the board connector (in dBm).
-    #[cfg(feature = "sx1302")]
     pub rf_power: i8,
-    /// Control of the digital gain of SX1301 (2 bits).
+    /// Control of the digital gain of the concentrator (2 bits).
     pub dig_gain: u8,
-    /// Control of the external PA (SX1301 I/O) (2 bits).
+    /// Control of the external PA (concentrator I/O) (2 bits).
     pub pa_gain: u8,
     /// Control of the radio DAC (2 bits).
     pub dac_gain: u8,
     /// control of the radio mixer (4 bits).
     pub mix_gain: u8,
-    /// Measured TX power at the board connector (in dBm).
-    #[cfg(feature = "sx1301")]
-    pub rf_power: i8,
-    /// (sx125x) calibrated I offset.
-    #[cfg(feature = "sx1302")]
+    /// (sx125x, SX1302 only) calibrated I offset.
     pub offset_i: i8,
-    /// (sx125x) calibrated Q offset.
-    #[cfg(feature = "sx1302")]
+    /// (sx125x, SX1302 only) calibrated Q offset.
     pub offset_q: i8,
-    /// 6 bits, (sx1250) control the radio power index to be used for configuration.
-    #[cfg(feature = "sx1302")]
+    /// (sx1250, SX1302 only) 6 bits, control the radio power index to
+    /// be used for configuration.
     pub pwr_id: u8,
 }
 
-/// Tx gain look-up-table.
-#[repr(C)]
-#[derive(Debug, Clone, Default)]
-pub struct TxGainLUT {
-    /// Array of Tx gain struct.
-    pub lut: [TxGain; 16],
-    /// Number of LUT indexes.
-    pub size: u8,
-}
-
 /// Concentrator's current TX availability.
 #[repr(u8)]
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -7,10 +7,13 @@ mod longfi;
 mod radio;
 pub use crate::longfi::*;
 pub use crate::radio::*;
+use std::time::{Duration, UNIX_EPOCH};
 
 impl From<loragw::Spreading> for Spreading {
     fn from(other: loragw::Spreading) -> Spreading {
         match other {
+            loragw::Spreading::SF5 => Spreading::SF5,
+            loragw::Spreading::SF6 => Spreading::SF6,
             loragw::Spreading::SF7 => Spreading::SF7,
             loragw::Spreading::SF8 => Spreading::SF8,
             loragw::Spreading::SF9 => Spreading::SF9,
@@ -25,6 +28,8 @@ impl From<loragw::Spreading> for Spreading {
 impl From<Spreading> for loragw::Spreading {
     fn from(other: Spreading) -> loragw::Spreading {
         match other {
+            Spreading::SF5 => loragw::Spreading::SF5,
+            Spreading::SF6 => loragw::Spreading::SF6,
             Spreading::SF7 => loragw::Spreading::SF7,
             Spreading::SF8 => loragw::Spreading::SF8,
             Spreading::SF9 => loragw::Spreading::SF9,
@@ -63,13 +68,9 @@ impl From<longfi::LongFiSpreading> for Spreading {
 impl From<loragw::Bandwidth> for Bandwidth {
     fn from(other: loragw::Bandwidth) -> Bandwidth {
         match other {
-            #[cfg(feature = "sx1301")]
             loragw::Bandwidth::BW7_8kHz => Bandwidth::BW7_8kHz,
-            #[cfg(feature = "sx1301")]
             loragw::Bandwidth::BW15_6kHz => Bandwidth::BW15_6kHz,
-            #[cfg(feature = "sx1301")]
             loragw::Bandwidth::BW31_2kHz => Bandwidth::BW31_2kHz,
-            #[cfg(feature = "sx1301")]
             loragw::Bandwidth::BW62_5kHz => Bandwidth::BW62_5kHz,
             loragw::Bandwidth::BW125kHz => Bandwidth::BW125kHz,
             loragw::Bandwidth::BW250kHz => Bandwidth::BW250kHz,
@@ -82,13 +83,9 @@ impl From<loragw::Bandwidth> for Bandwidth {
 impl From<Bandwidth> for loragw::Bandwidth {
     fn from(other: Bandwidth) -> loragw::Bandwidth {
         match other {
-            #[cfg(feature = "sx1301")]
             Bandwidth::BW7_8kHz => loragw::Bandwidth::BW7_8kHz,
-            #[cfg(feature = "sx1301")]
             Bandwidth::BW15_6kHz => loragw::Bandwidth::BW15_6kHz,
-            #[cfg(feature = "sx1301")]
             Bandwidth::BW31_2kHz => loragw::Bandwidth::BW31_2kHz,
-            #[cfg(feature = "sx1301")]
             Bandwidth::BW62_5kHz => loragw::Bandwidth::BW62_5kHz,
             Bandwidth::BW125kHz => loragw::Bandwidth::BW125kHz,
             Bandwidth::BW250kHz => loragw::Bandwidth::BW250kHz,
@@ -140,6 +137,20 @@ impl From<Radio> for loragw::Radio {
     }
 }
 
+impl From<&loragw::Error> for RadioTxFailure {
+    fn from(other: &loragw::Error) -> RadioTxFailure {
+        match other {
+            loragw::Error::LBT => RadioTxFailure::LBT_DENIED,
+            loragw::Error::QueueFull => RadioTxFailure::QUEUE_FULL,
+            loragw::Error::TooLate => RadioTxFailure::TOO_LATE,
+            loragw::Error::Collision => RadioTxFailure::COLLISION,
+            loragw::Error::DutyCycle => RadioTxFailure::DUTY_CYCLE_EXCEEDED,
+            loragw::Error::DwellTime => RadioTxFailure::DWELL_TIME_EXCEEDED,
+            _ => RadioTxFailure::TX_FAILURE_UNDEFINED,
+        }
+    }
+}
+
 impl From<loragw::RxPacketLoRa> for RadioRxPacket {
     fn from(other: loragw::RxPacketLoRa) -> RadioRxPacket {
         RadioRxPacket {
@@ -157,6 +168,25 @@ impl From<loragw::RxPacketLoRa> for RadioRxPacket {
             rssi: other.rssi,
             snr: other.snr,
             payload: other.payload,
+            utc: other
+                .utc
+                .and_then(|utc| utc.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |utc| utc.as_micros() as u64),
+            gps_time: other
+                .gps_time
+                .map_or(0, |gps_time| gps_time.as_micros() as u64),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<loragw::VersionInfo> for RadioStatusResp {
+    fn from(other: loragw::VersionInfo) -> RadioStatusResp {
+        RadioStatusResp {
+            hal_version: other.hal,
+            chip: other.chip.to_string(),
+            chip_version: other.chip_version.map_or(0, u32::from),
+            fpga_version: other.fpga_version.map_or(0, u32::from),
             ..Default::default()
         }
     }
@@ -166,7 +196,11 @@ impl From<RadioTxReq> for loragw::TxPacketLoRa {
     fn from(other: RadioTxReq) -> loragw::TxPacketLoRa {
         loragw::TxPacketLoRa {
             freq: other.freq,
-            mode: loragw::TxMode::Immediate,
+            // A zero timestamp requests the next free slot.
+            mode: match other.timestamp {
+                0 => loragw::TxMode::Immediate,
+                ts => loragw::TxMode::Timestamp(Duration::from_micros(ts)),
+            },
             radio: other.radio.into(),
             power: other.power as i8,
             bandwidth: other.bandwidth.into(),
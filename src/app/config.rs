@@ -0,0 +1,246 @@
+use crate::{cfg, cmdline, error::AppResult};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+};
+
+/// Sections `cfg::Config::load` fills in from a region's channel plan.
+const PLAN_SECTIONS: [&str; 4] = [
+    "radios",
+    "multirate_channels",
+    "lora_std_channel",
+    "fsk_channel",
+];
+
+pub fn config(args: cmdline::Config) -> AppResult {
+    match args {
+        cmdline::Config::Check(args) => check(args),
+        cmdline::Config::Show(args) => show(args),
+    }
+}
+
+fn check(args: cmdline::ConfigCheck) -> AppResult {
+    // Loading validates the config.
+    let (chip, _) = cfg::Config::load(Some(&args.cfg_file), args.chip)?;
+    println!("{}: valid {} config", args.cfg_file.display(), chip);
+    Ok(())
+}
+
+/// `config show`'s JSON output.
+#[derive(Serialize)]
+struct Shown<'a> {
+    config: &'a cfg::Config,
+    /// Where each value came from, keyed by its path.
+    sources: &'a BTreeMap<String, String>,
+    /// Frequency of each channel, in Hz, keyed by the path of its IF.
+    frequencies: &'a BTreeMap<String, i64>,
+}
+
+fn show(args: cmdline::ConfigShow) -> AppResult {
+    let (chip, mut cfg) = cfg::Config::load(args.cfg_file.as_deref(), args.chip)?;
+    let (origin, written) = match args.cfg_file {
+        Some(ref path) => (path.display().to_string(), fs::read_to_string(path)?),
+        None => (
+            format!("built-in {} default", chip),
+            cfg::Config::default_toml(chip).to_string(),
+        ),
+    };
+    let written: toml::Value = toml::from_str(&written)?;
+    let written_values = flatten(&written);
+
+    // Show the chip actually used, even if the config leaves it out.
+    let chip_source = match (args.chip, &cfg.board.chip) {
+        (Some(_), _) => "--chip".to_string(),
+        (None, Some(_)) => origin.clone(),
+        (None, None) => "default".to_string(),
+    };
+    cfg.board.chip = Some(chip.to_string());
+
+    let region = cfg.region()?;
+    let effective = toml::Value::try_from(&cfg).map_err(|e| e.to_string())?;
+    let mut sources = BTreeMap::new();
+    for (path, _) in flatten(&effective) {
+        let section = path.split(|c| c == '.' || c == '[').next().unwrap_or("");
+        let source = match region {
+            _ if path == "board.chip" => chip_source.clone(),
+            _ if written_values.contains_key(&path) => origin.clone(),
+            Some(region) if PLAN_SECTIONS.contains(&section) && written.get(section).is_none() => {
+                format!("region {} plan", region)
+            }
+            _ => "default".to_string(),
+        };
+        sources.insert(path, source);
+    }
+    let frequencies = channel_freqs(&effective);
+
+    match args.format {
+        cmdline::Format::Toml => {
+            let toml = toml::to_string(&cfg).map_err(|e| e.to_string())?;
+            print!("{}", annotate(&toml, &sources, &frequencies));
+        }
+        cmdline::Format::Json => {
+            let shown = Shown {
+                config: &cfg,
+                sources: &sources,
+                frequencies: &frequencies,
+            };
+            let json = serde_json::to_string_pretty(&shown).map_err(|e| e.to_string())?;
+            println!("{}", json);
+        }
+    }
+    Ok(())
+}
+
+/// A value which differs between two configs.
+pub(super) struct Change {
+    /// Path of the value, e.g. `radios[0].freq`.
+    pub path: String,
+    pub old: Option<toml::Value>,
+    pub new: Option<toml::Value>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{}: {} -> {}", self.path, old, new),
+            (Some(old), None) => write!(f, "{}: {} removed", self.path, old),
+            (None, Some(new)) => write!(f, "{}: {} added", self.path, new),
+            (None, None) => write!(f, "{}: unchanged", self.path),
+        }
+    }
+}
+
+/// Returns every value which differs between `old` and `new`.
+pub(super) fn diff(old: &cfg::Config, new: &cfg::Config) -> AppResult<Vec<Change>> {
+    let flat = |cfg| {
+        toml::Value::try_from(cfg)
+            .map(|value| flatten(&value))
+            .map_err(|e| e.to_string())
+    };
+    let (mut old, mut new) = (flat(old)?, flat(new)?);
+    let paths: BTreeSet<String> = old.keys().chain(new.keys()).cloned().collect();
+    Ok(paths
+        .into_iter()
+        .filter_map(|path| {
+            let (old, new) = (old.remove(&path), new.remove(&path));
+            if old == new {
+                None
+            } else {
+                Some(Change { path, old, new })
+            }
+        })
+        .collect())
+}
+
+/// Returns all values in `value`, keyed by their path, e.g.
+/// `radios[0].freq`.
+fn flatten(value: &toml::Value) -> BTreeMap<String, toml::Value> {
+    fn walk(value: &toml::Value, path: String, out: &mut BTreeMap<String, toml::Value>) {
+        match value {
+            toml::Value::Table(table) => {
+                for (key, value) in table {
+                    let path = if path.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", path, key)
+                    };
+                    walk(value, path, out);
+                }
+            }
+            toml::Value::Array(array)
+                if !array.is_empty() && array.iter().all(|v| v.is_table()) =>
+            {
+                for (i, value) in array.iter().enumerate() {
+                    walk(value, format!("{}[{}]", path, i), out);
+                }
+            }
+            _ => {
+                out.insert(path, value.clone());
+            }
+        }
+    }
+    let mut out = BTreeMap::new();
+    walk(value, String::new(), &mut out);
+    out
+}
+
+/// Returns the frequency, in Hz, of each channel in `cfg`, keyed by the
+/// path of its IF.
+fn channel_freqs(cfg: &toml::Value) -> BTreeMap<String, i64> {
+    let centers: BTreeMap<i64, i64> = cfg
+        .get("radios")
+        .and_then(toml::Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|radio| {
+            Some((
+                radio.get("id")?.as_integer()?,
+                radio.get("freq")?.as_integer()?,
+            ))
+        })
+        .collect();
+    let mut channels = Vec::new();
+    if let Some(array) = cfg
+        .get("multirate_channels")
+        .and_then(toml::Value::as_array)
+    {
+        for (i, channel) in array.iter().enumerate() {
+            channels.push((format!("multirate_channels[{}]", i), channel));
+        }
+    }
+    for section in &["lora_std_channel", "fsk_channel"] {
+        if let Some(channel) = cfg.get(section) {
+            channels.push((section.to_string(), channel));
+        }
+    }
+    channels
+        .into_iter()
+        .filter_map(|(path, channel)| {
+            let center = centers.get(&channel.get("radio")?.as_integer()?)?;
+            let if_ = channel.get("if")?.as_integer()?;
+            Some((format!("{}.if", path), center + if_))
+        })
+        .collect()
+}
+
+/// Appends the source of each value in `toml`, as written by
+/// `toml::to_string`, to its line as a comment, along with the
+/// frequency of channel IFs.
+fn annotate(
+    toml: &str,
+    sources: &BTreeMap<String, String>,
+    frequencies: &BTreeMap<String, i64>,
+) -> String {
+    let mut out = String::new();
+    // Path of the table the current line is in.
+    let mut table = String::new();
+    // Number of each array of tables' entries seen so far.
+    let mut entries: BTreeMap<&str, usize> = BTreeMap::new();
+    for line in toml.lines() {
+        out.push_str(line);
+        if line.starts_with("[[") && line.ends_with("]]") {
+            let name = &line[2..line.len() - 2];
+            let n = entries.entry(name).or_insert(0);
+            table = format!("{}[{}]", name, n);
+            *n += 1;
+        } else if line.starts_with('[') && line.ends_with(']') {
+            table = line[1..line.len() - 1].to_string();
+        } else if let Some(key) = line.split(" = ").next() {
+            let path = if table.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}.{}", table, key)
+            };
+            if let Some(source) = sources.get(&path) {
+                out.push_str("  # ");
+                out.push_str(source);
+            }
+            if let Some(freq) = frequencies.get(&path) {
+                out.push_str(&format!(", {} Hz", freq));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -1,15 +1,25 @@
-use crate::error::AppResult;
+use super::reset_concentrator;
+use crate::{cfg, cmdline, error::AppResult};
 
-#[cfg(feature = "sx1301")]
-pub fn connect() -> AppResult {
-    loragw::Concentrator::open()?.connect()?;
+pub fn connect(args: cmdline::Connect) -> AppResult {
+    let (chip, cfg) = cfg::Config::load(args.cfg_file.as_deref(), args.chip)?;
+    let path = &cfg.board.spidev_path;
+    let concentrator = loragw::Concentrator::open(chip, path)?;
+    reset_concentrator(cfg.board.reset.as_ref())?;
+    concentrator.connect(path)?;
+    if args.verbose {
+        print_version_info(&concentrator.version_info()?);
+    }
     Ok(())
 }
 
-#[cfg(feature = "sx1302")]
-pub fn connect() -> AppResult {
-    use ::std::ffi::CString;
-    let path = CString::new("/dev/spidev0.0").unwrap();
-    loragw::Concentrator::open()?.connect(&path)?;
-    Ok(())
+fn print_version_info(info: &loragw::VersionInfo) {
+    println!("hal:  {}", info.hal);
+    match info.chip_version {
+        Some(version) => println!("chip: {} (version {})", info.chip, version),
+        None => println!("chip: {}", info.chip),
+    }
+    if let Some(version) = info.fpga_version {
+        println!("fpga: version {}", version);
+    }
 }
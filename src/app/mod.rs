@@ -1,23 +1,29 @@
-use crate::error::AppResult;
+use crate::{cfg, cmdline, error::AppResult};
+use loragw::ConfigurableBackend;
 use protobuf::Message;
 use std::{
+    convert::{TryFrom, TryInto},
     fmt,
     net::{SocketAddr, UdpSocket},
 };
 
 mod bist;
+mod config;
 mod connect;
 mod listen;
 mod longfi;
 mod longfi_test;
+mod reset;
 mod send;
 mod serve;
 
 pub use self::bist::*;
+pub use self::config::*;
 pub use self::connect::*;
 pub use self::listen::*;
 pub use self::longfi::*;
 pub use self::longfi_test::*;
+pub use self::reset::*;
 pub use self::send::*;
 pub use self::serve::*;
 
@@ -36,3 +42,116 @@ fn msg_send<T: Message>(msg: T, socket: &UdpSocket, addr: SocketAddr) -> AppResu
     socket.send_to(&enc_buf, addr)?;
     Ok(())
 }
+
+/// Opens the selected concentrator on `board`, resetting hardware
+/// with its `reset`.
+///
+/// The simulated concentrator always generates the default
+/// `SimTraffic`.
+fn open_backend(
+    backend: cmdline::Backend,
+    chip: loragw::Chip,
+    board: &cfg::Board,
+) -> AppResult<Box<dyn ConfigurableBackend>> {
+    Ok(match backend {
+        cmdline::Backend::Hardware => {
+            let concentrator = loragw::Concentrator::open(chip, &board.spidev_path)?;
+            reset_concentrator(board.reset.as_ref())?;
+            Box::new(concentrator)
+        }
+        cmdline::Backend::Sim => Box::new(loragw::SimConcentrator::new(
+            chip,
+            loragw::SimTraffic::default(),
+        )),
+    })
+}
+
+/// Pulses the concentrator's reset line, if it has one.
+///
+/// Call with the concentrator open, so another process using it can't
+/// be reset from under it.
+fn reset_concentrator(reset: Option<&cfg::Reset>) -> AppResult {
+    match reset {
+        Some(reset) => Ok(loragw::reset::reset(&reset.try_into()?)?),
+        None => {
+            log::debug!("no [board.reset], not resetting concentrator");
+            Ok(())
+        }
+    }
+}
+
+fn configure(
+    concentrator: &dyn ConfigurableBackend,
+    chip: loragw::Chip,
+    cfg: cfg::Config,
+) -> AppResult {
+    log::debug!("configuring concentrator with {:?}", cfg);
+    let region = cfg.region()?;
+
+    concentrator.config_board(&cfg.board.try_into()?)?;
+
+    if let Some(radios) = cfg.radios {
+        for c in radios {
+            concentrator.config_rx_rf(&loragw::RxRFConf::try_from(c)?)?;
+        }
+    }
+
+    // Chains the config leaves out are disabled, as `serve` may be
+    // reapplying a config over one which enabled them.
+    let multirate_channels = cfg.multirate_channels.unwrap_or_default();
+    for chain in 0..chip.lora_std_chain() {
+        let conf = match multirate_channels.get(chain as usize) {
+            Some(c) => loragw::ChannelConf::try_from(c)?,
+            None => loragw::ChannelConf::Disable,
+        };
+        concentrator.config_channel(chain, &conf)?;
+    }
+
+    let conf = match cfg.lora_std_channel {
+        Some(ref c) => loragw::ChannelConf::try_from(c)?,
+        None => loragw::ChannelConf::Disable,
+    };
+    concentrator.config_channel(chip.lora_std_chain(), &conf)?;
+
+    let conf = match cfg.fsk_channel {
+        Some(ref c) => loragw::ChannelConf::try_from(c)?,
+        None => loragw::ChannelConf::Disable,
+    };
+    concentrator.config_channel(chip.fsk_chain(), &conf)?;
+
+    // Like the chains above, LUTs, LBT and regional limits the config
+    // leaves out are reset to the HAL's defaults.
+    //
+    // Entries make up one LUT per radio. The SX1301's single LUT is
+    // radio 0's.
+    let mut luts: [Vec<loragw::TxGain>; 2] = Default::default();
+    for g in cfg.tx_gains.unwrap_or_default() {
+        luts[loragw::Radio::try_from(g.radio)? as usize].push(g.into());
+    }
+    for (radio, gains) in [loragw::Radio::R0, loragw::Radio::R1].iter().zip(&luts) {
+        let own_lut = match radio {
+            loragw::Radio::R0 => true,
+            loragw::Radio::R1 => !chip.shared_tx_path(),
+        };
+        if !gains.is_empty() {
+            concentrator.config_tx_gain(*radio, gains)?;
+        } else if own_lut {
+            concentrator.config_tx_gain(*radio, &chip.default_tx_gains())?;
+        }
+    }
+
+    let lbt = match cfg.lbt {
+        Some(lbt) => lbt.into(),
+        None => loragw::LBTConf {
+            enable: false,
+            rssi_target: 0,
+            rssi_offset: 0,
+            channels: Vec::new(),
+        },
+    };
+    concentrator.config_lbt(&lbt)?;
+
+    concentrator.config_region(region)?;
+
+    Ok(())
+}
@@ -0,0 +1,14 @@
+use super::reset_concentrator;
+use crate::{cfg, cmdline, error::AppResult};
+
+pub fn reset(args: cmdline::Reset) -> AppResult {
+    let (chip, cfg) = cfg::Config::load(args.cfg_file.as_deref(), args.chip)?;
+    let reset = match cfg.board.reset {
+        Some(ref reset) => reset,
+        None => return Err("config has no [board.reset] section".to_string().into()),
+    };
+    // Hold the concentrator so it isn't reset from under another
+    // process.
+    let _concentrator = loragw::Concentrator::open(chip, &cfg.board.spidev_path)?;
+    reset_concentrator(Some(reset))
+}
@@ -25,6 +25,8 @@ pub fn send(args: cmdline::Send) -> AppResult {
             }
         },
         spreading: match args.spreading {
+            5 => msg::Spreading::SF5,
+            6 => msg::Spreading::SF6,
             7 => msg::Spreading::SF7,
             8 => msg::Spreading::SF8,
             9 => msg::Spreading::SF9,
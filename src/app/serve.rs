@@ -1,17 +1,38 @@
-use super::{msg_send, print_at_level};
+use super::{config, configure, msg_send, open_backend, print_at_level, reset_concentrator};
 use crate::{cfg, cmdline, error::AppResult};
-use loragw;
+use loragw::{self, jit::JitQueue, ConcentratorBackend, TxStatus};
 use messages::*;
 use protobuf::parse_from_bytes;
+use signal_hook::{consts::SIGHUP, flag};
 use std::{
-    convert::{TryFrom, TryInto},
-    fs,
+    collections::BTreeSet,
+    f32,
     io::ErrorKind,
     net::UdpSocket,
-    path::PathBuf,
-    time::Duration,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+/// Maximum number of downlinks waiting for their slot.
+const JIT_QUEUE_SIZE: usize = 32;
+
+/// How often to read the board temperature.
+const TEMPERATURE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often to log statistics.
+const STATS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Longest a reloaded config waits for queued and in-flight
+/// transmissions to go out before being applied.
+const RELOAD_DRAIN: Duration = Duration::from_secs(10);
+
+/// How long to wait between attempts to reopen a concentrator which
+/// failed to restart or reopen.
+const RESTART_RETRY: Duration = Duration::from_secs(5);
+
 pub fn serve(args: cmdline::Serve) -> AppResult {
     let socket = {
         assert_ne!(args.listen_addr_in, args.publish_addr_out);
@@ -20,16 +41,169 @@ pub fn serve(args: cmdline::Serve) -> AppResult {
         UdpSocket::bind(args.listen_addr_in)?
     };
 
-    socket.set_read_timeout(Some(Duration::from_millis(args.interval)))?;
+    let interval = Duration::from_millis(args.interval);
+    socket.set_read_timeout(Some(interval))?;
     let mut req_buf = [0; 1024];
 
-    let mut concentrator = loragw::Concentrator::open()?;
-    config(&mut concentrator, args.cfg_file)?;
-    concentrator.start()?;
+    let (chip, cfg) = cfg::Config::load(args.cfg_file.as_deref(), args.chip)?;
+    log::info!("using {} concentrator", chip);
+    let concentrator = open_backend(args.backend, chip, &cfg.board)?;
+    let mut gps = match cfg.gps {
+        Some(ref gps) => Some(loragw::gps::Gps::open(
+            &gps.tty_path,
+            gps.family.as_deref(),
+        )?),
+        None => None,
+    };
+    let mut clamp = cfg.tx_power_clamp.clone();
+    // Config the concentrator is running with, to diff reloads against.
+    let mut running = cfg.clone();
+    configure(concentrator.as_ref(), chip, cfg)?;
+    // `None` while the concentrator is down after failing to restart.
+    let mut concentrator = Some(concentrator.start()?);
+    // When to next try reopening the concentrator, while it's down.
+    let mut reopen_at = Instant::now();
+    let started = Instant::now();
+
+    // Set on SIGHUP, to reload the config.
+    let hup = Arc::new(AtomicBool::new(false));
+    flag::register(SIGHUP, Arc::clone(&hup))?;
+    // Reloaded config waiting to be applied, and when it was loaded.
+    let mut reload: Option<(cfg::Config, Instant)> = None;
+
+    let mut thermometer = Thermometer::new();
+    if let Some(concentrator) = concentrator.as_deref() {
+        thermometer.poll(concentrator);
+    }
+    if clamp.is_some() && thermometer.celsius.is_none() {
+        log::warn!("board temperature unavailable, TX power won't be clamped");
+    }
+    let mut stats = Stats::default();
+    let mut stats_since = Instant::now();
+
+    // Transmit requests, tagged with their request id and radio,
+    // waiting for their slot. Every packet is given a timestamp, so
+    // this also satisfies the HAL when LBT is enabled.
+    let mut jit = JitQueue::new(JIT_QUEUE_SIZE, chip);
+
+    // Request id of the most recent transmission handed to each radio.
+    // The concentrator holds one packet per TX path at a time, so these
+    // are the only ones which may still be aborted.
+    let mut last_tx: [Option<u32>; 2] = [None; 2];
 
     loop {
-        while let Some(packets) = concentrator.receive()? {
-            for pkt in packets {
+        if hup.swap(false, Ordering::Relaxed) {
+            log::info!("SIGHUP received, reloading config");
+            match reload_cfg(&args, chip, &running) {
+                // Replaces any config still waiting to be applied,
+                // without extending its wait.
+                Ok(Some(cfg)) if needs_restart(&running, &cfg) => {
+                    let loaded = reload.map_or_else(Instant::now, |(_, loaded)| loaded);
+                    reload = Some((cfg, loaded));
+                }
+                Ok(Some(cfg)) => {
+                    log::info!("applied reloaded config");
+                    clamp = cfg.tx_power_clamp.clone();
+                    running = cfg;
+                    reload = None;
+                }
+                Ok(None) => {
+                    log::info!("config unchanged");
+                    reload = None;
+                }
+                Err(e) => log::error!("not reloading config: {}", e),
+            }
+        }
+
+        // The counter restarts with the concentrator, so a reloaded
+        // config waits for transmissions timed against it to go out.
+        // New TX requests are refused meanwhile.
+        if let Some((_, loaded)) = reload {
+            let mut idle = jit.is_empty();
+            if let Some(concentrator) = concentrator.as_deref() {
+                for &radio in &[loragw::Radio::R0, loragw::Radio::R1] {
+                    idle &= concentrator.transmit_status(radio)? == TxStatus::Free;
+                }
+            }
+            if idle || loaded.elapsed() >= RELOAD_DRAIN {
+                let (cfg, _) = reload.take().unwrap();
+                while let Some(((id, _), _)) = jit.remove(|_| true) {
+                    log::warn!("dropping TX request {} to apply reloaded config", id);
+                    stats.tx_failed += 1;
+                    msg_send(
+                        tx_resp(id, Err(loragw::Error::TooLate)),
+                        &socket,
+                        args.publish_addr_out,
+                    )?;
+                }
+                jit = JitQueue::new(JIT_QUEUE_SIZE, chip);
+                last_tx = [None; 2];
+                // `cfg` gets one attempt. If it fails, the concentrator
+                // is reopened with `running`.
+                let res = match concentrator.take() {
+                    Some(concentrator) => try_restart(&args, concentrator, chip, &cfg, &running),
+                    None => reopen(&args, chip, &cfg).map(|concentrator| (concentrator, true)),
+                };
+                match res {
+                    Ok((restarted, applied)) => {
+                        concentrator = Some(restarted);
+                        if applied {
+                            log::info!("applied reloaded config");
+                            clamp = cfg.tx_power_clamp.clone();
+                            running = cfg;
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("failed to restart concentrator: {}", e);
+                        reopen_at = Instant::now();
+                    }
+                }
+                if let Some(gps) = gps.as_mut() {
+                    gps.reset_time_ref();
+                }
+            }
+        }
+
+        if concentrator.is_none() && reopen_at <= Instant::now() {
+            match reopen(&args, chip, &running) {
+                Ok(reopened) => {
+                    log::info!("reopened concentrator");
+                    concentrator = Some(reopened);
+                    if let Some(gps) = gps.as_mut() {
+                        gps.reset_time_ref();
+                    }
+                }
+                Err(e) => {
+                    log::error!(
+                        "failed to reopen concentrator, retrying in {}s: {}",
+                        RESTART_RETRY.as_secs(),
+                        e
+                    );
+                    reopen_at = Instant::now() + RESTART_RETRY;
+                }
+            }
+        }
+        let backend = concentrator.as_deref();
+
+        if let (Some(gps), Some(concentrator)) = (gps.as_mut(), backend) {
+            gps.poll(|| concentrator.pps_counter())?;
+        }
+
+        if let Some(concentrator) = backend {
+            thermometer.poll(concentrator);
+        }
+        if stats_since.elapsed() >= STATS_INTERVAL {
+            stats.log(thermometer.celsius);
+            stats = Stats::default();
+            stats_since = Instant::now();
+        }
+
+        while let Some(packets) = backend.map_or(Ok(None), |c| c.receive())? {
+            for mut pkt in packets {
+                if let (Some(gps), loragw::RxPacket::LoRa(pkt)) = (&gps, &mut pkt) {
+                    gps.stamp(pkt);
+                }
+                stats.rx += 1;
                 print_at_level(args.print_level, &pkt);
                 if let loragw::RxPacket::LoRa(pkt) = pkt {
                     log::debug!("received {:?}", pkt);
@@ -43,96 +217,452 @@ pub fn serve(args: cmdline::Serve) -> AppResult {
             }
         }
 
+        // Hand due packets to the concentrator, answering their
+        // requests.
+        while let Some(((id, radio), res)) = backend.map_or(Ok(None), |c| jit.poll(c))? {
+            if res.is_ok() {
+                stats.tx += 1;
+                // On a shared TX path, this packet replaced the other
+                // radio's.
+                if chip.shared_tx_path() {
+                    last_tx = [None; 2];
+                }
+                last_tx[radio as usize] = Some(id);
+            } else {
+                stats.tx_failed += 1;
+            }
+            msg_send(tx_resp(id, res), &socket, args.publish_addr_out)?;
+        }
+
+        // Wake up in time for the next queued packet.
+        let mut timeout = interval;
+        if let (false, Some(concentrator)) = (jit.is_empty(), backend) {
+            if let Some(due) = jit.next_due(concentrator.counter()?) {
+                timeout = due.min(interval).max(Duration::from_millis(1));
+            }
+        }
+        socket.set_read_timeout(Some(timeout))?;
+
         match socket.recv(&mut req_buf) {
             Ok(sz) => {
                 let resp = match parse_from_bytes::<RadioReq>(&req_buf[..sz]) {
                     Ok(req) => match req {
-                        // Valid TX request
+                        // TX request while the concentrator is down, or
+                        // waiting to restart with a reloaded config
+                        RadioReq {
+                            id,
+                            kind: Some(RadioReq_oneof_kind::tx(_)),
+                            ..
+                        } if backend.is_none() || reload.is_some() => {
+                            log::warn!("TX request {} refused, concentrator unavailable", id);
+                            stats.tx_failed += 1;
+                            Some(tx_unavailable_resp(id))
+                        }
+                        // Valid TX request, answered once the packet
+                        // is handed to the concentrator
                         RadioReq {
                             id,
                             kind: Some(RadioReq_oneof_kind::tx(req)),
                             ..
                         } => {
-                            let pkt = req.into();
-                            log::debug!("transmitting {:?}", pkt);
-                            match concentrator.transmit(loragw::TxPacket::LoRa(pkt)) {
-                                Ok(()) => RadioResp {
+                            let concentrator = backend.unwrap();
+                            let mut pkt: loragw::TxPacketLoRa = req.into();
+                            if let Some(clamp) = &clamp {
+                                pkt.power = clamp.apply(pkt.power, thermometer.celsius);
+                            }
+                            let radio = pkt.radio;
+                            log::debug!("queueing {:?}", pkt);
+                            let res = if chip.supports_spreading(pkt.spreading) {
+                                concentrator.counter().and_then(|now| {
+                                    jit.enqueue(now, loragw::TxPacket::LoRa(pkt), (id, radio))
+                                })
+                            } else {
+                                log::error!("{:?} requires an SX1302", pkt.spreading);
+                                Err(loragw::Error::Unsupported)
+                            };
+                            match res {
+                                Ok(()) => None,
+                                Err(e) => {
+                                    stats.tx_failed += 1;
+                                    Some(tx_resp(id, Err(e)))
+                                }
+                            }
+                        }
+                        // Cancel a previous TX request
+                        RadioReq {
+                            id,
+                            kind: Some(RadioReq_oneof_kind::cancel(req)),
+                            ..
+                        } => {
+                            let success = match jit.remove(|&(id, _)| id == req.id) {
+                                Some(_) => {
+                                    log::debug!("removed TX request {} from queue", req.id);
+                                    true
+                                }
+                                None => match backend {
+                                    Some(concentrator) => {
+                                        cancel(concentrator, &mut last_tx, req.id)
+                                    }
+                                    None => {
+                                        log::warn!("no pending TX request {} to cancel", req.id);
+                                        false
+                                    }
+                                },
+                            };
+                            Some(RadioResp {
+                                id,
+                                kind: Some(RadioResp_oneof_kind::cancel(RadioCancelResp {
+                                    success,
+                                    ..Default::default()
+                                })),
+                                ..Default::default()
+                            })
+                        }
+                        // Read the concentrator's counter
+                        RadioReq {
+                            id,
+                            kind: Some(RadioReq_oneof_kind::counter(_)),
+                            ..
+                        } => match up(backend).and_then(|c| counter(c, started)) {
+                            Ok(resp) => Some(RadioResp {
+                                id,
+                                kind: Some(RadioResp_oneof_kind::counter(resp)),
+                                ..Default::default()
+                            }),
+                            Err(e) => {
+                                log::error!("failed to read counter: {}", e);
+                                Some(RadioResp {
                                     id,
-                                    kind: Some(RadioResp_oneof_kind::tx(RadioTxResp {
-                                        success: true,
-                                        ..Default::default()
-                                    })),
+                                    kind: None,
                                     ..Default::default()
-                                },
-                                Err(_) => RadioResp {
+                                })
+                            }
+                        },
+                        // Report HAL and hardware versions, and board
+                        // temperature
+                        RadioReq {
+                            id,
+                            kind: Some(RadioReq_oneof_kind::status(_)),
+                            ..
+                        } => match up(backend).and_then(|c| Ok(c.version_info()?)) {
+                            Ok(info) => {
+                                let mut status = RadioStatusResp::from(info);
+                                status.temperature = thermometer.celsius.unwrap_or(f32::NAN);
+                                Some(RadioResp {
                                     id,
-                                    kind: Some(RadioResp_oneof_kind::tx(RadioTxResp {
-                                        success: false,
-                                        ..Default::default()
-                                    })),
+                                    kind: Some(RadioResp_oneof_kind::status(status)),
                                     ..Default::default()
-                                },
+                                })
                             }
-                        }
+                            Err(e) => {
+                                log::error!("failed to read version info: {}", e);
+                                Some(RadioResp {
+                                    id,
+                                    kind: None,
+                                    ..Default::default()
+                                })
+                            }
+                        },
                         // Invalid request
                         RadioReq { id, kind: None, .. } => {
                             log::error!("request {} empty", id);
-                            RadioResp {
+                            Some(RadioResp {
                                 id,
                                 kind: None,
                                 ..Default::default()
-                            }
+                            })
                         }
                     },
                     Err(e) => {
                         log::error!("parse Req error {:?} from {:x?}", e, &req_buf[..sz]);
-                        RadioResp {
+                        Some(RadioResp {
                             id: 0,
                             kind: Some(RadioResp_oneof_kind::parse_err(Vec::from(&req_buf[..sz]))),
                             ..Default::default()
-                        }
+                        })
                     }
                 };
-                msg_send(resp, &socket, args.publish_addr_out)?;
+                if let Some(resp) = resp {
+                    msg_send(resp, &socket, args.publish_addr_out)?;
+                }
             }
-            Err(ref e) if e.kind() == ErrorKind::WouldBlock => (),
+            // Interrupted by SIGHUP, handled at the top of the loop.
+            Err(ref e)
+                if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::Interrupted => {}
             Err(e) => return Err(e.into()),
         }
     }
 }
 
-fn config(concentrator: &mut loragw::Concentrator, cfg: Option<PathBuf>) -> AppResult {
-    let cfg = match cfg {
-        Some(path) => {
-            let cfg_str = fs::read_to_string(path)?;
-            cfg::Config::from_str(&cfg_str)?
+/// Loads `serve`'s config again, logging how it differs from
+/// `running`.
+///
+/// Returns `None` if nothing changed, and fails if the new config is
+/// invalid or changes something only a restart of `serve` can.
+fn reload_cfg(
+    args: &cmdline::Serve,
+    chip: loragw::Chip,
+    running: &cfg::Config,
+) -> AppResult<Option<cfg::Config>> {
+    let path = match args.cfg_file {
+        Some(ref path) => path,
+        None => return Err("serve was started without --config".to_string().into()),
+    };
+    let (new_chip, cfg) = cfg::Config::load(Some(path), args.chip)?;
+    if new_chip != chip {
+        return Err(format!("chip changed from {} to {}", chip, new_chip).into());
+    }
+    let changes = config::diff(running, &cfg)?;
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    for change in &changes {
+        log::info!("config changed, {}", change);
+    }
+
+    // The GPS is only set up on open, and the reset line is pulsed
+    // on every restart, so must stay the one the board was opened
+    // with. Radios can't be configured without their type, so can't
+    // be disabled again once enabled.
+    let radios = |cfg: &cfg::Config| -> BTreeSet<u32> {
+        cfg.radios.iter().flatten().map(|radio| radio.id).collect()
+    };
+    let mut fixed = Vec::new();
+    if cfg.gps != running.gps {
+        fixed.push("[gps] changed");
+    }
+    if cfg.board.reset != running.board.reset {
+        fixed.push("[board.reset] changed");
+    }
+    if radios(&cfg) != radios(running) {
+        fixed.push("[[radios]] added or removed");
+    }
+    if !fixed.is_empty() {
+        return Err(format!("{}, restart serve to apply", fixed.join(", ")).into());
+    }
+    Ok(Some(cfg))
+}
+
+/// Returns `true` if switching from `running` to `cfg` requires
+/// restarting the concentrator, i.e. changes more than the TX power
+/// clamp, which `serve` applies itself.
+fn needs_restart(running: &cfg::Config, cfg: &cfg::Config) -> bool {
+    let mut cfg = cfg.clone();
+    cfg.tx_power_clamp = running.tx_power_clamp.clone();
+    cfg != *running
+}
+
+/// Stops `concentrator`, applies `cfg` and starts it again.
+///
+/// If applying `cfg` fails, `running` is reapplied instead. Returns
+/// the restarted concentrator, and whether `cfg` was applied. Fails,
+/// closing the concentrator, if it can't be stopped or started.
+fn try_restart(
+    args: &cmdline::Serve,
+    concentrator: Box<dyn ConcentratorBackend>,
+    chip: loragw::Chip,
+    cfg: &cfg::Config,
+    running: &cfg::Config,
+) -> AppResult<(Box<dyn ConcentratorBackend>, bool)> {
+    let concentrator = concentrator.stop()?;
+    // Like on open, the hardware starts from reset. `reload_cfg`
+    // keeps `[board.reset]` from changing.
+    if args.backend == cmdline::Backend::Hardware {
+        reset_concentrator(running.board.reset.as_ref())?;
+    }
+    let applied = match configure(concentrator.as_ref(), chip, cfg.clone()) {
+        Ok(()) => true,
+        Err(e) => {
+            log::error!("failed to apply reloaded config, reverting: {}", e);
+            configure(concentrator.as_ref(), chip, running.clone())?;
+            false
         }
-        None => cfg::Config::from_str_or_default(None)?,
     };
+    Ok((concentrator.start()?, applied))
+}
+
+/// Opens the concentrator again and starts it with `cfg`.
+fn reopen(
+    args: &cmdline::Serve,
+    chip: loragw::Chip,
+    cfg: &cfg::Config,
+) -> AppResult<Box<dyn ConcentratorBackend>> {
+    let concentrator = open_backend(args.backend, chip, &cfg.board)?;
+    configure(concentrator.as_ref(), chip, cfg.clone())?;
+    Ok(concentrator.start()?)
+}
 
-    log::debug!("configuring concentrator with {:?}", cfg);
+/// Returns the running concentrator, failing if it's down.
+fn up(concentrator: Option<&dyn ConcentratorBackend>) -> AppResult<&dyn ConcentratorBackend> {
+    concentrator.ok_or_else(|| "concentrator is down".to_string().into())
+}
+
+/// Returns the response to TX request `id`.
+fn tx_resp(id: u32, res: loragw::Result) -> RadioResp {
+    let tx = match res {
+        Ok(()) => RadioTxResp {
+            success: true,
+            ..Default::default()
+        },
+        Err(e) => {
+            log::warn!("TX request {} failed: {}", id, e);
+            RadioTxResp {
+                success: false,
+                failure: (&e).into(),
+                ..Default::default()
+            }
+        }
+    };
+    RadioResp {
+        id,
+        kind: Some(RadioResp_oneof_kind::tx(tx)),
+        ..Default::default()
+    }
+}
 
-    concentrator.config_board(&cfg.board.try_into()?)?;
+/// Returns the response to TX request `id`, refused because the
+/// concentrator is down or restarting.
+fn tx_unavailable_resp(id: u32) -> RadioResp {
+    RadioResp {
+        id,
+        kind: Some(RadioResp_oneof_kind::tx(RadioTxResp {
+            success: false,
+            failure: RadioTxFailure::UNAVAILABLE,
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+}
 
-    if let Some(radios) = cfg.radios {
-        for c in radios {
-            concentrator.config_rx_rf(&loragw::RxRFConf::try_from(c)?)?;
+/// Aborts the transmission requested by `target`, returning `true` if
+/// it was still pending.
+fn cancel(
+    concentrator: &dyn ConcentratorBackend,
+    last_tx: &mut [Option<u32>; 2],
+    target: u32,
+) -> bool {
+    let radio = match [loragw::Radio::R0, loragw::Radio::R1]
+        .iter()
+        .find(|&&radio| last_tx[radio as usize] == Some(target))
+    {
+        Some(&radio) => radio,
+        None => {
+            log::warn!("no pending TX request {} to cancel", target);
+            return false;
+        }
+    };
+    last_tx[radio as usize] = None;
+    match concentrator.transmit_status(radio) {
+        Ok(TxStatus::Free) => {
+            log::warn!("TX request {} already sent", target);
+            return false;
+        }
+        Ok(_) => (),
+        Err(e) => {
+            log::error!("failed to cancel TX request {}: {}", target, e);
+            return false;
         }
     }
+    log::debug!("cancelling TX request {}", target);
+    // Only the SX1302 can abort a single radio. The SX1301 has one TX
+    // path, so aborting everything only affects `target`.
+    let res = match concentrator.abort_tx_on(radio) {
+        Err(loragw::Error::Unsupported) => concentrator.abort_tx(),
+        res => res,
+    };
+    match res {
+        Ok(()) => true,
+        Err(e) => {
+            log::error!("failed to cancel TX request {}: {}", target, e);
+            false
+        }
+    }
+}
+
+/// Samples the concentrator's counter along with host monotonic time
+/// (since `started`) and wall-clock time, all in microseconds.
+fn counter(
+    concentrator: &dyn ConcentratorBackend,
+    started: Instant,
+) -> AppResult<RadioCounterResp> {
+    let monotonic = started.elapsed();
+    let wall_clock = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let counter = concentrator.counter()?;
+    Ok(RadioCounterResp {
+        counter: counter.as_micros() as u64,
+        monotonic: monotonic.as_micros() as u64,
+        wall_clock: wall_clock.as_micros() as u64,
+        ..Default::default()
+    })
+}
+
+/// Periodically reads the board temperature.
+struct Thermometer {
+    /// Most recent reading, in °C.
+    celsius: Option<f32>,
+    /// When to read next, or `None` if the board has no sensor.
+    next_read: Option<Instant>,
+}
 
-    if let Some(multirate_channels) = cfg.multirate_channels {
-        for (i, c) in multirate_channels.iter().enumerate() {
-            concentrator.config_channel(i as u8, &loragw::ChannelConf::try_from(c)?)?;
+impl Thermometer {
+    fn new() -> Self {
+        Thermometer {
+            celsius: None,
+            next_read: Some(Instant::now()),
         }
     }
 
-    if let Some(gains) = cfg.tx_gains {
-        let gains: Vec<loragw::TxGain> = gains
-            .iter()
-            .map(|g| loragw::TxGain::from(g.clone()))
-            .collect();
-        concentrator.config_tx_gain(gains.as_slice())?
+    /// Reads the temperature if `TEMPERATURE_INTERVAL` has passed
+    /// since the last reading.
+    ///
+    /// A failed reading keeps the previous one.
+    fn poll(&mut self, concentrator: &dyn ConcentratorBackend) {
+        match self.next_read {
+            Some(next_read) if next_read <= Instant::now() => (),
+            _ => return,
+        }
+        match concentrator.temperature() {
+            Ok(celsius) => {
+                log::debug!("board temperature {:.1} °C", celsius);
+                self.celsius = Some(celsius);
+            }
+            Err(loragw::Error::Unsupported) => {
+                log::info!("board has no temperature sensor");
+                self.next_read = None;
+                return;
+            }
+            Err(e) => log::error!("failed to read board temperature: {}", e),
+        }
+        self.next_read = Some(Instant::now() + TEMPERATURE_INTERVAL);
     }
+}
+
+/// Counts logged every `STATS_INTERVAL`.
+#[derive(Debug, Default)]
+struct Stats {
+    /// Packets received.
+    rx: u64,
+    /// Packets handed to the concentrator.
+    tx: u64,
+    /// TX requests which failed.
+    tx_failed: u64,
+}
 
-    Ok(())
+impl Stats {
+    fn log(&self, temperature: Option<f32>) {
+        let temperature = match temperature {
+            Some(celsius) => format!("{:.1} °C", celsius),
+            None => "unknown".to_string(),
+        };
+        log::info!(
+            "last {}s: {} packets received, {} transmitted, {} TX failures, board temperature {}",
+            STATS_INTERVAL.as_secs(),
+            self.rx,
+            self.tx,
+            self.tx_failed,
+            temperature
+        );
+    }
 }
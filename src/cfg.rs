@@ -1,38 +1,145 @@
 use crate::error::{AppError, AppResult};
-use serde::{Deserialize, Serialize};
-use std::{convert::TryFrom, ffi::CString};
+use serde::{Deserialize, Serialize, Serializer};
+use std::{
+    convert::TryFrom,
+    ffi::CString,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use toml;
 
-#[cfg(feature = "sx1301")]
-static DEFAULT_CFG_TOML: &str = include_str!("../default_config_sx1301.toml");
+mod check;
+mod plan;
 
-#[cfg(feature = "sx1302")]
-static DEFAULT_CFG_TOML: &str = include_str!("../default_config_sx1302.toml");
+static DEFAULT_CFG_TOML_SX1301: &str = include_str!("../default_config_sx1301.toml");
+
+static DEFAULT_CFG_TOML_SX1302: &str = include_str!("../default_config_sx1302.toml");
 
 /// Represents top-level configuration document.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Config {
+    /// Region [EU868,US915,AU915,AS923-1,IN865,KR920]. Its limits are
+    /// enforced on transmissions, and its channel plan fills in
+    /// channel and radio sections left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Sub-band [1,8] of the US915 and AU915 channel plans.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub_band: Option<u32>,
     pub board: Board,
     pub radios: Option<Vec<Radio>>,
     pub multirate_channels: Option<Vec<MultirateLoraChannel>>,
+    pub lora_std_channel: Option<LoraStdChannel>,
+    pub fsk_channel: Option<FskChannel>,
     pub tx_gains: Option<Vec<TxGain>>,
+    pub gps: Option<Gps>,
+    pub lbt: Option<Lbt>,
+    pub tx_power_clamp: Option<TxPowerClamp>,
 }
 
 impl Config {
-    pub fn from_str_or_default(cfg: Option<&str>) -> AppResult<Self> {
-        Self::from_str(cfg.unwrap_or(DEFAULT_CFG_TOML))
+    pub fn from_str_or_default(cfg: Option<&str>, chip: loragw::Chip) -> AppResult<Self> {
+        Self::from_str(cfg.unwrap_or_else(|| Self::default_toml(chip)))
+    }
+
+    /// Returns the built-in config for `chip`, used without a config
+    /// file.
+    pub fn default_toml(chip: loragw::Chip) -> &'static str {
+        match chip {
+            loragw::Chip::SX1301 => DEFAULT_CFG_TOML_SX1301,
+            loragw::Chip::SX1302 => DEFAULT_CFG_TOML_SX1302,
+        }
     }
 
     pub fn from_str(cfg: &str) -> AppResult<Self> {
         Ok(toml::from_str(cfg)?)
     }
+
+    pub fn region(&self) -> AppResult<Option<loragw::regulatory::Region>> {
+        match self.region {
+            Some(ref region) => match region.parse() {
+                Ok(region) => Ok(Some(region)),
+                Err(_) => Err(format!("invalid region {:?}", region).into()),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Checks this config, as applied to `chip`, failing with
+    /// `AppError::Invalid` listing every problem found.
+    pub fn validate(&self, chip: loragw::Chip) -> AppResult {
+        let problems = check::check(self, chip);
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Invalid(problems))
+        }
+    }
+
+    /// Loads the config file at `path`, or the built-in default, and
+    /// returns it along with the chip it should be applied to.
+    ///
+    /// `chip` (typically from the command line) takes precedence over
+    /// `[board] chip`. Sections left out are filled in from the
+    /// channel plan of `region`, if set, and the result is validated.
+    pub fn load(
+        path: Option<&Path>,
+        chip: Option<loragw::Chip>,
+    ) -> AppResult<(loragw::Chip, Self)> {
+        let (chip, mut cfg) = match path {
+            Some(path) => {
+                let cfg = Self::from_str(&fs::read_to_string(path)?)?;
+                let chip = match (chip, cfg.board.chip()?) {
+                    (Some(chip), Some(cfg_chip)) if chip != cfg_chip => {
+                        log::warn!("overriding config chip {} with {}", cfg_chip, chip);
+                        chip
+                    }
+                    (chip, cfg_chip) => chip.or(cfg_chip).unwrap_or_default(),
+                };
+                (chip, cfg)
+            }
+            None => {
+                let chip = chip.unwrap_or_default();
+                (chip, Self::from_str_or_default(None, chip)?)
+            }
+        };
+        plan::expand(&mut cfg, chip)?;
+        cfg.validate(chip)?;
+        Ok((chip, cfg))
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Board {
+    /// Concentrator chip [sx1301,sx1302].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chip: Option<String>,
     pub lorawan_public: bool,
     pub clksrc: u32,
+    #[serde(serialize_with = "serialize_cstring")]
     pub spidev_path: CString,
+    /// GPIO reset performed before the concentrator is started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reset: Option<Reset>,
+}
+
+/// Serializes `s` as a string, rather than bytes, as it is written in
+/// config files.
+fn serialize_cstring<S: Serializer>(s: &CString, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&s.to_string_lossy())
+}
+
+impl Board {
+    pub fn chip(&self) -> AppResult<Option<loragw::Chip>> {
+        match self.chip {
+            Some(ref chip) => match chip.parse() {
+                Ok(chip) => Ok(Some(chip)),
+                Err(_) => Err(format!("invalid board chip {:?}", chip).into()),
+            },
+            None => Ok(None),
+        }
+    }
 }
 
 impl TryFrom<Board> for loragw::BoardConf {
@@ -46,6 +153,132 @@ impl TryFrom<Board> for loragw::BoardConf {
     }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Reset {
+    /// Global sysfs GPIO number of the reset line.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpio: Option<u32>,
+    /// GPIO character device of the reset line, e.g. "/dev/gpiochip0".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpiochip: Option<PathBuf>,
+    /// Offset of the reset line on `gpiochip`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    /// The concentrator is held in reset while the line is low.
+    #[serde(default)]
+    pub active_low: bool,
+    /// Time to hold the concentrator in reset.
+    #[serde(default = "Reset::default_ms")]
+    pub pulse_ms: u64,
+    /// Time to wait before asserting, and after releasing, reset.
+    #[serde(default = "Reset::default_ms")]
+    pub settle_ms: u64,
+}
+
+impl Reset {
+    fn default_ms() -> u64 {
+        100
+    }
+}
+
+impl TryFrom<&Reset> for loragw::reset::ResetConf {
+    type Error = AppError;
+    fn try_from(other: &Reset) -> AppResult<Self> {
+        let line = match (other.gpio, &other.gpiochip, other.offset) {
+            (Some(number), None, None) => loragw::reset::GpioLine::Sysfs(number),
+            (None, Some(path), Some(offset)) => loragw::reset::GpioLine::Chip {
+                path: path.clone(),
+                offset,
+            },
+            _ => {
+                return Err("[board.reset] needs either gpio, or gpiochip and offset"
+                    .to_string()
+                    .into())
+            }
+        };
+        Ok(Self {
+            line,
+            active_low: other.active_low,
+            pulse: Duration::from_millis(other.pulse_ms),
+            settle: Duration::from_millis(other.settle_ms),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Gps {
+    /// Serial port the GPS receiver is connected to.
+    pub tty_path: PathBuf,
+    /// Receiver family, e.g. "ubx7".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Lbt {
+    /// RSSI (in dBm) above which a channel is considered busy.
+    pub rssi_target: i8,
+    /// RSSI correction applied to the LBT radio's readings.
+    #[serde(default)]
+    pub rssi_offset: i8,
+    pub channels: Vec<LbtChannel>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LbtChannel {
+    pub freq: u32,
+    /// Listen time in uS [128,5000].
+    pub scan_time_us: u16,
+}
+
+impl From<Lbt> for loragw::LBTConf {
+    fn from(other: Lbt) -> loragw::LBTConf {
+        loragw::LBTConf {
+            enable: true,
+            rssi_target: other.rssi_target,
+            rssi_offset: other.rssi_offset,
+            channels: other
+                .channels
+                .into_iter()
+                .map(|c| loragw::LBTChanConf {
+                    freq: c.freq,
+                    scan_time_us: c.scan_time_us,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TxPowerClamp {
+    /// Board temperature (in °C) above which transmit power is
+    /// clamped.
+    pub above_temp_c: f32,
+    /// Highest transmit power (in dBm) allowed above `above_temp_c`.
+    pub max_power_dbm: i8,
+}
+
+impl TxPowerClamp {
+    /// Returns the power to transmit at instead of `power` (in dBm),
+    /// given the last board temperature reading.
+    ///
+    /// Power is left alone while the temperature is unknown.
+    pub fn apply(&self, power: i8, temperature: Option<f32>) -> i8 {
+        match temperature {
+            Some(temp) if temp > self.above_temp_c && power > self.max_power_dbm => {
+                log::warn!(
+                    "board at {:.1} °C, clamping TX power from {} to {} dBm",
+                    temp,
+                    power,
+                    self.max_power_dbm
+                );
+                self.max_power_dbm
+            }
+            _ => power,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Radio {
     pub id: u32,
@@ -88,8 +321,88 @@ impl TryFrom<&MultirateLoraChannel> for loragw::ChannelConf {
     }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LoraStdChannel {
+    radio: u32,
+    #[serde(rename(serialize = "if", deserialize = "if"))]
+    if_: i32,
+    /// Bandwidth in Hz [125000,250000,500000].
+    bandwidth: u32,
+    /// Spreading factor [5,6,7,8,9,10,11,12]. 5 and 6 require an
+    /// SX1302.
+    spreading: u8,
+}
+
+impl TryFrom<&LoraStdChannel> for loragw::ChannelConf {
+    type Error = AppError;
+    fn try_from(other: &LoraStdChannel) -> AppResult<loragw::ChannelConf> {
+        Ok(loragw::ChannelConf::Fixed {
+            radio: loragw::Radio::try_from(other.radio)?,
+            freq: other.if_,
+            bandwidth: bandwidth(other.bandwidth)?,
+            spreading: spreading(other.spreading)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FskChannel {
+    radio: u32,
+    #[serde(rename(serialize = "if", deserialize = "if"))]
+    if_: i32,
+    /// Bandwidth in Hz [125000,250000,500000].
+    bandwidth: u32,
+    /// Datarate in bits per second.
+    datarate: u32,
+    /// Size of the sync word in bytes, 0 for the HAL's default.
+    #[serde(default)]
+    sync_word_size: u8,
+    #[serde(default)]
+    sync_word: u64,
+}
+
+impl TryFrom<&FskChannel> for loragw::ChannelConf {
+    type Error = AppError;
+    fn try_from(other: &FskChannel) -> AppResult<loragw::ChannelConf> {
+        Ok(loragw::ChannelConf::FSK {
+            radio: loragw::Radio::try_from(other.radio)?,
+            freq: other.if_,
+            bandwidth: bandwidth(other.bandwidth)?,
+            datarate: other.datarate,
+            sync_word_size: other.sync_word_size,
+            sync_word: other.sync_word,
+        })
+    }
+}
+
+fn bandwidth(hz: u32) -> AppResult<loragw::Bandwidth> {
+    Ok(match hz {
+        125_000 => loragw::Bandwidth::BW125kHz,
+        250_000 => loragw::Bandwidth::BW250kHz,
+        500_000 => loragw::Bandwidth::BW500kHz,
+        invalid => return Err(format!("{} is not a valid bandwidth", invalid).into()),
+    })
+}
+
+fn spreading(sf: u8) -> AppResult<loragw::Spreading> {
+    Ok(match sf {
+        5 => loragw::Spreading::SF5,
+        6 => loragw::Spreading::SF6,
+        7 => loragw::Spreading::SF7,
+        8 => loragw::Spreading::SF8,
+        9 => loragw::Spreading::SF9,
+        10 => loragw::Spreading::SF10,
+        11 => loragw::Spreading::SF11,
+        12 => loragw::Spreading::SF12,
+        invalid => return Err(format!("{} is not a valid spreading factor", invalid).into()),
+    })
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct TxGain {
+    /// Radio whose LUT this entry belongs to.
+    #[serde(default)]
+    pub radio: u32,
     #[serde(rename(serialize = "dbm", deserialize = "dbm"))]
     pub rf_power: i8,
     #[serde(rename(serialize = "dig", deserialize = "dig"))]
@@ -108,11 +421,8 @@ impl From<TxGain> for loragw::TxGain {
             dac_gain: 3,
             mix_gain: other.mix_gain,
             rf_power: other.rf_power,
-            #[cfg(feature = "sx1302")]
             offset_i: 0,
-            #[cfg(feature = "sx1302")]
             offset_q: 0,
-            #[cfg(feature = "sx1302")]
             pwr_id: 0,
         }
     }
@@ -0,0 +1,267 @@
+//! Semantic checks of a configuration, run before it is applied.
+//!
+//! The HALs only report a generic failure for most invalid
+//! configurations, often as late as `lgw_start`. These checks name
+//! the field at fault instead.
+
+use super::Config;
+use loragw::{reset::ResetConf, Bandwidth, Chip, RadioType};
+use std::{convert::TryFrom, fmt::Display};
+
+/// Number of multirate IF chains on either chip.
+const MAX_MULTIRATE_CHANNELS: usize = 8;
+
+/// Largest TX gain LUT the HALs accept.
+const MAX_TX_GAINS: usize = 16;
+
+/// Problems found in a config, each prefixed with the field at fault.
+#[derive(Default)]
+struct Problems(Vec<String>);
+
+impl Problems {
+    fn add(&mut self, field: impl Display, problem: impl Display) {
+        self.0.push(format!("{}: {}", field, problem));
+    }
+}
+
+/// Returns every problem found in `cfg`, as applied to `chip`.
+pub(super) fn check(cfg: &Config, chip: Chip) -> Vec<String> {
+    let mut problems = Problems::default();
+
+    // Center frequencies of the enabled radios.
+    let mut centers = [None; 2];
+    for (i, radio) in cfg.radios.iter().flatten().enumerate() {
+        let field = format!("radios[{}]", i);
+        match centers.get_mut(radio.id as usize) {
+            None => problems.add(
+                format!("{}.id", field),
+                format!("{} is not a radio [0,1]", radio.id),
+            ),
+            Some(Some(_)) => problems.add(
+                format!("{}.id", field),
+                format!("radio {} is already configured", radio.id),
+            ),
+            Some(center) => *center = Some(radio.freq),
+        }
+        if RadioType::try_from(radio.type_.as_str()).is_err() {
+            problems.add(
+                format!("{}.type", field),
+                format!("{:?} is not a radio type", radio.type_),
+            );
+        }
+    }
+
+    match centers.get(cfg.board.clksrc as usize) {
+        None => problems.add(
+            "board.clksrc",
+            format!("{} is not a radio [0,1]", cfg.board.clksrc),
+        ),
+        Some(None) => problems.add(
+            "board.clksrc",
+            format!("radio {} is not enabled in [[radios]]", cfg.board.clksrc),
+        ),
+        Some(Some(_)) => (),
+    }
+    if let Some(reset) = &cfg.board.reset {
+        if ResetConf::try_from(reset).is_err() {
+            problems.add("board.reset", "needs either gpio, or gpiochip and offset");
+        }
+    }
+
+    if let Some(channels) = &cfg.multirate_channels {
+        if channels.len() > MAX_MULTIRATE_CHANNELS {
+            problems.add(
+                "multirate_channels",
+                format!(
+                    "{} channels, at most {} are supported",
+                    channels.len(),
+                    MAX_MULTIRATE_CHANNELS
+                ),
+            );
+        }
+        for (i, c) in channels.iter().enumerate() {
+            let field = format!("multirate_channels[{}]", i);
+            check_if(
+                &mut problems,
+                &field,
+                chip,
+                &centers,
+                c.radio,
+                c.if_,
+                Bandwidth::BW125kHz,
+            );
+        }
+    }
+
+    if let Some(c) = &cfg.lora_std_channel {
+        let field = "lora_std_channel";
+        if let Some(bandwidth) = check_bandwidth(&mut problems, field, c.bandwidth) {
+            check_if(
+                &mut problems,
+                field,
+                chip,
+                &centers,
+                c.radio,
+                c.if_,
+                bandwidth,
+            );
+        }
+        match super::spreading(c.spreading) {
+            Ok(spreading) if !chip.supports_spreading(spreading) => problems.add(
+                format!("{}.spreading", field),
+                format!("{} requires an SX1302", c.spreading),
+            ),
+            Ok(_) => (),
+            Err(_) => problems.add(
+                format!("{}.spreading", field),
+                format!("{} is not a spreading factor [5,12]", c.spreading),
+            ),
+        }
+    }
+
+    if let Some(c) = &cfg.fsk_channel {
+        let field = "fsk_channel";
+        if let Some(bandwidth) = check_bandwidth(&mut problems, field, c.bandwidth) {
+            check_if(
+                &mut problems,
+                field,
+                chip,
+                &centers,
+                c.radio,
+                c.if_,
+                bandwidth,
+            );
+        }
+    }
+
+    if let Some(gains) = &cfg.tx_gains {
+        // Number of entries, and the last entry's power, per radio.
+        let mut luts = [(0, None); 2];
+        for (i, gain) in gains.iter().enumerate() {
+            let field = format!("tx_gains[{}]", i);
+            let (len, last) = match luts.get_mut(gain.radio as usize) {
+                Some(lut) => lut,
+                None => {
+                    problems.add(
+                        format!("{}.radio", field),
+                        format!("{} is not a radio [0,1]", gain.radio),
+                    );
+                    continue;
+                }
+            };
+            if chip == Chip::SX1301 && gain.radio != 0 {
+                problems.add(
+                    format!("{}.radio", field),
+                    "the SX1301 has a single LUT, for radio 0",
+                );
+            }
+            *len += 1;
+            match *last {
+                Some(last) if gain.rf_power <= last => problems.add(
+                    format!("{}.dbm", field),
+                    format!(
+                        "{} dBm doesn't increase on radio {}'s previous entry, {} dBm",
+                        gain.rf_power, gain.radio, last
+                    ),
+                ),
+                _ => (),
+            }
+            *last = Some(gain.rf_power);
+        }
+        for (radio, (len, _)) in luts.iter().enumerate() {
+            if *len > MAX_TX_GAINS {
+                problems.add(
+                    "tx_gains",
+                    format!(
+                        "radio {} has {} entries, at most {} are supported",
+                        radio, len, MAX_TX_GAINS
+                    ),
+                );
+            }
+        }
+    }
+
+    if let Some(lbt) = &cfg.lbt {
+        if chip != Chip::SX1301 {
+            problems.add("lbt", "only the SX1301 supports listen-before-talk");
+        }
+        if lbt.channels.is_empty() || lbt.channels.len() > 8 {
+            problems.add(
+                "lbt.channels",
+                format!("{} channels, 1 to 8 are supported", lbt.channels.len()),
+            );
+        }
+        for (i, c) in lbt.channels.iter().enumerate() {
+            if c.scan_time_us != 128 && c.scan_time_us != 5000 {
+                problems.add(
+                    format!("lbt.channels[{}].scan_time_us", i),
+                    format!("{} is not a scan time [128,5000]", c.scan_time_us),
+                );
+            }
+        }
+    }
+
+    problems.0
+}
+
+/// Returns the bandwidth `hz` of the channel at `field`, if valid.
+fn check_bandwidth(problems: &mut Problems, field: &str, hz: u32) -> Option<Bandwidth> {
+    match super::bandwidth(hz) {
+        Ok(bandwidth) => Some(bandwidth),
+        Err(_) => {
+            problems.add(
+                format!("{}.bandwidth", field),
+                format!("{} Hz is not a bandwidth [125000,250000,500000]", hz),
+            );
+            None
+        }
+    }
+}
+
+/// Checks that the channel at `field` is on an enabled radio, and
+/// within its passband.
+fn check_if(
+    problems: &mut Problems,
+    field: &str,
+    chip: Chip,
+    centers: &[Option<u32>; 2],
+    radio: u32,
+    if_: i32,
+    bandwidth: Bandwidth,
+) {
+    match centers.get(radio as usize) {
+        Some(Some(_)) => (),
+        Some(None) => {
+            problems.add(
+                format!("{}.radio", field),
+                format!("radio {} is not enabled in [[radios]]", radio),
+            );
+            return;
+        }
+        None => {
+            problems.add(
+                format!("{}.radio", field),
+                format!("{} is not a radio [0,1]", radio),
+            );
+            return;
+        }
+    }
+    let channel_hz = match bandwidth {
+        Bandwidth::BW500kHz => 500_000,
+        Bandwidth::BW250kHz => 250_000,
+        _ => 125_000,
+    };
+    let max_if = (chip.rx_bandwidth(bandwidth) - channel_hz) / 2;
+    if i64::from(if_).abs() > i64::from(max_if) {
+        problems.add(
+            format!("{}.if", field),
+            format!(
+                "{} Hz is outside radio {}'s passband, {} kHz channels must be within ±{} Hz of its center",
+                if_,
+                radio,
+                channel_hz / 1000,
+                max_if
+            ),
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! Built-in regional channel plans.
+//!
+//! A plan fills in whichever of `radios`, `multirate_channels`,
+//! `lora_std_channel` and `fsk_channel` the config leaves out, so
+//! explicit sections override the plan.
+
+use super::{Config, FskChannel, LoraStdChannel, MultirateLoraChannel, Radio};
+use crate::error::AppResult;
+use loragw::{regulatory::Region, Chip};
+
+/// Frequencies making up a plan, in Hz.
+#[derive(Debug)]
+struct Channels {
+    /// Center frequencies of radios 0 and 1.
+    radios: [u32; 2],
+    /// Multirate channels.
+    multirate: Vec<u32>,
+    /// LoRa channel frequency, bandwidth and spreading factor.
+    lora_std: Option<(u32, u32, u8)>,
+    /// FSK channel frequency, bandwidth and datarate.
+    fsk: Option<(u32, u32, u32)>,
+}
+
+impl Channels {
+    /// Returns the channels of `region`'s plan, using `sub_band` of
+    /// US915 and AU915, which have 8 of them.
+    fn new(region: Region, sub_band: Option<u32>) -> AppResult<Self> {
+        let sub_band = match (region, sub_band) {
+            (Region::US915, Some(n)) | (Region::AU915, Some(n)) if (1..=8).contains(&n) => n,
+            (Region::US915, _) | (Region::AU915, _) => {
+                return Err(format!("region {} needs a sub_band [1,8]", region).into())
+            }
+            (_, Some(_)) => return Err(format!("region {} has no sub-bands", region).into()),
+            (_, None) => 0,
+        };
+        Ok(match region {
+            Region::EU868 => Channels {
+                radios: [867_500_000, 868_500_000],
+                multirate: vec![
+                    868_100_000,
+                    868_300_000,
+                    868_500_000,
+                    867_100_000,
+                    867_300_000,
+                    867_500_000,
+                    867_700_000,
+                    867_900_000,
+                ],
+                lora_std: Some((868_300_000, 250_000, 7)),
+                fsk: Some((868_800_000, 125_000, 50_000)),
+            },
+            Region::US915 | Region::AU915 => {
+                // Sub-bands are 8 125 kHz channels 200 kHz apart, and
+                // the 500 kHz channel between their 4th and 5th.
+                let first = match region {
+                    Region::US915 => 902_300_000,
+                    _ => 915_200_000,
+                } + (sub_band - 1) * 1_600_000;
+                Channels {
+                    radios: [first + 400_000, first + 1_100_000],
+                    multirate: (0..8).map(|n| first + n * 200_000).collect(),
+                    lora_std: Some((first + 700_000, 500_000, 8)),
+                    fsk: None,
+                }
+            }
+            Region::AS923_1 => Channels {
+                radios: [922_100_000, 923_000_000],
+                multirate: vec![
+                    923_200_000,
+                    923_400_000,
+                    922_000_000,
+                    922_200_000,
+                    922_400_000,
+                    922_600_000,
+                    922_800_000,
+                    923_000_000,
+                ],
+                lora_std: Some((922_100_000, 250_000, 7)),
+                fsk: Some((921_800_000, 125_000, 50_000)),
+            },
+            Region::IN865 => Channels {
+                radios: [866_385_000, 865_232_500],
+                multirate: vec![
+                    865_062_500,
+                    865_402_500,
+                    865_985_000,
+                    866_185_000,
+                    866_385_000,
+                    866_585_000,
+                    866_785_000,
+                ],
+                lora_std: Some((866_550_000, 250_000, 7)),
+                fsk: None,
+            },
+            Region::KR920 => Channels {
+                radios: [922_400_000, 923_100_000],
+                multirate: vec![
+                    922_100_000,
+                    922_300_000,
+                    922_500_000,
+                    922_700_000,
+                    922_900_000,
+                    923_100_000,
+                    923_300_000,
+                ],
+                lora_std: None,
+                fsk: None,
+            },
+        })
+    }
+
+    /// Returns the radio whose center is nearest to `freq`, and
+    /// `freq`'s offset from it.
+    fn tune(&self, freq: u32) -> (u32, i32) {
+        let offset = |radio: usize| freq as i32 - self.radios[radio] as i32;
+        let radio = if offset(0).abs() <= offset(1).abs() {
+            0
+        } else {
+            1
+        };
+        (radio as u32, offset(radio))
+    }
+
+    /// Returns the radios, which transmit on radio 0, of the type and
+    /// RSSI offset the built-in config for `chip` uses.
+    fn radios(&self, chip: Chip) -> Vec<Radio> {
+        let (type_, rssi_offset) = match chip {
+            Chip::SX1301 => ("SX1257", -169.0),
+            Chip::SX1302 => ("SX1250", -207.0),
+        };
+        self.radios
+            .iter()
+            .enumerate()
+            .map(|(id, &freq)| Radio {
+                id: id as u32,
+                freq,
+                rssi_offset,
+                type_: type_.to_string(),
+                tx_enable: id == 0,
+            })
+            .collect()
+    }
+
+    fn multirate_channels(&self) -> Vec<MultirateLoraChannel> {
+        self.multirate
+            .iter()
+            .map(|&freq| {
+                let (radio, if_) = self.tune(freq);
+                MultirateLoraChannel { radio, if_ }
+            })
+            .collect()
+    }
+
+    fn lora_std_channel(&self) -> Option<LoraStdChannel> {
+        self.lora_std.map(|(freq, bandwidth, spreading)| {
+            let (radio, if_) = self.tune(freq);
+            LoraStdChannel {
+                radio,
+                if_,
+                bandwidth,
+                spreading,
+            }
+        })
+    }
+
+    fn fsk_channel(&self) -> Option<FskChannel> {
+        self.fsk.map(|(freq, bandwidth, datarate)| {
+            let (radio, if_) = self.tune(freq);
+            FskChannel {
+                radio,
+                if_,
+                bandwidth,
+                datarate,
+                sync_word_size: 0,
+                sync_word: 0,
+            }
+        })
+    }
+}
+
+/// Fills in the sections of `cfg` left out, from the plan of its
+/// `region`, if any.
+pub(super) fn expand(cfg: &mut Config, chip: Chip) -> AppResult {
+    let region = match cfg.region()? {
+        Some(region) => region,
+        None if cfg.sub_band.is_some() => return Err("sub_band needs a region".to_string().into()),
+        None => return Ok(()),
+    };
+    let mut channels = Channels::new(region, cfg.sub_band)?;
+    log::debug!("{} channel plan: {:?}", region, channels);
+    // Explicit sections, even empty ones, replace the plan's.
+    let explicit = |section: &str| log::info!("{} overrides {} channel plan", section, region);
+    match cfg.radios {
+        Some(ref radios) => {
+            explicit("[[radios]]");
+            // Place the plan's channels relative to these instead.
+            for radio in radios {
+                if let Some(center) = channels.radios.get_mut(radio.id as usize) {
+                    *center = radio.freq;
+                }
+            }
+        }
+        None => cfg.radios = Some(channels.radios(chip)),
+    }
+    match cfg.multirate_channels {
+        Some(_) => explicit("[[multirate_channels]]"),
+        None => cfg.multirate_channels = Some(channels.multirate_channels()),
+    }
+    match cfg.lora_std_channel {
+        Some(_) => explicit("[lora_std_channel]"),
+        None => cfg.lora_std_channel = channels.lora_std_channel(),
+    }
+    match cfg.fsk_channel {
+        Some(_) => explicit("[fsk_channel]"),
+        None => cfg.fsk_channel = channels.fsk_channel(),
+    }
+    Ok(())
+}
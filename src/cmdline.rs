@@ -1,4 +1,4 @@
-use std::{net::SocketAddr, path::PathBuf};
+use std::{net::SocketAddr, path::PathBuf, str::FromStr};
 use structopt::{clap, StructOpt};
 
 #[derive(Debug, StructOpt)]
@@ -6,7 +6,7 @@ pub enum Cmd {
     /// Built In Self Test. Configures, starts, then immediately stops
     /// concentrator. Returns 1 on hardware failure.
     #[structopt(name = "bist", raw(setting = "clap::AppSettings::Hidden"))]
-    Bist,
+    Bist(Bist),
 
     /// Operate as a consumer of another instance running as the
     /// server. This mode is primarily meant for debugging and
@@ -37,7 +37,145 @@ pub enum Cmd {
     /// Check connection to concentrator chip and immediately exit
     /// with status code.
     #[structopt(name = "connect")]
-    Connect,
+    Connect(Connect),
+
+    /// Reset the concentrator using the config's `[board.reset]` GPIO
+    /// line, and exit.
+    #[structopt(name = "reset")]
+    Reset(Reset),
+
+    /// Inspect configuration files without touching the concentrator.
+    #[structopt(name = "config")]
+    Config(Config),
+}
+
+/// Selects which concentrator implementation to run against.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// Real SPI-connected concentrator hardware.
+    Hardware,
+    /// Pure-software simulated concentrator.
+    Sim,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hw" => Ok(Backend::Hardware),
+            "sim" => Ok(Backend::Sim),
+            invalid => Err(format!("{} is not a valid backend", invalid)),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Bist {
+    /// Concentrator backend to test [hw,sim].
+    #[structopt(
+        long = "backend",
+        default_value = "hw",
+        raw(possible_values = r#"&["hw", "sim"]"#)
+    )]
+    pub backend: Backend,
+
+    #[structopt(short = "c", long = "config", parse(from_os_str))]
+    pub cfg_file: Option<PathBuf>,
+
+    /// Concentrator chip [sx1301,sx1302]. Overrides `[board] chip`.
+    #[structopt(long = "chip", raw(possible_values = r#"&["sx1301", "sx1302"]"#))]
+    pub chip: Option<loragw::Chip>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Connect {
+    /// Concentrator chip [sx1301,sx1302]. Overrides `[board] chip`.
+    #[structopt(long = "chip", raw(possible_values = r#"&["sx1301", "sx1302"]"#))]
+    pub chip: Option<loragw::Chip>,
+
+    /// Config file, for `[board.reset]`.
+    #[structopt(short = "c", long = "config", parse(from_os_str))]
+    pub cfg_file: Option<PathBuf>,
+
+    /// Print HAL, chip, and FPGA versions after connecting.
+    #[structopt(short = "v", long = "verbose")]
+    pub verbose: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Reset {
+    #[structopt(short = "c", long = "config", parse(from_os_str))]
+    pub cfg_file: Option<PathBuf>,
+
+    /// Concentrator chip [sx1301,sx1302]. Overrides `[board] chip`.
+    #[structopt(long = "chip", raw(possible_values = r#"&["sx1301", "sx1302"]"#))]
+    pub chip: Option<loragw::Chip>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Config {
+    /// Check a config file for errors, and exit with status 1 if any
+    /// are found.
+    #[structopt(name = "check")]
+    Check(ConfigCheck),
+
+    /// Print the configuration `serve` would run with, after
+    /// defaults, region expansion and overrides, noting where each
+    /// value came from.
+    #[structopt(name = "show")]
+    Show(ConfigShow),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ConfigCheck {
+    /// Config file to check.
+    #[structopt(value_name = "FILE", parse(from_os_str))]
+    pub cfg_file: PathBuf,
+
+    /// Concentrator chip [sx1301,sx1302] to check against. Overrides
+    /// `[board] chip`.
+    #[structopt(long = "chip", raw(possible_values = r#"&["sx1301", "sx1302"]"#))]
+    pub chip: Option<loragw::Chip>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ConfigShow {
+    /// Config file, or the built-in default if omitted.
+    #[structopt(short = "c", long = "config", parse(from_os_str))]
+    pub cfg_file: Option<PathBuf>,
+
+    /// Concentrator chip [sx1301,sx1302]. Overrides `[board] chip`.
+    #[structopt(long = "chip", raw(possible_values = r#"&["sx1301", "sx1302"]"#))]
+    pub chip: Option<loragw::Chip>,
+
+    /// Output format [toml,json].
+    #[structopt(
+        long = "format",
+        default_value = "toml",
+        raw(possible_values = r#"&["toml", "json"]"#)
+    )]
+    pub format: Format,
+}
+
+/// Selects how `config show` prints the configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// TOML, with each value's origin in a trailing comment.
+    Toml,
+    /// A JSON object holding the configuration, and each value's
+    /// origin keyed by its path.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "toml" => Ok(Format::Toml),
+            "json" => Ok(Format::Json),
+            invalid => Err(format!("{} is not a valid format", invalid)),
+        }
+    }
 }
 
 #[derive(Debug, StructOpt)]
@@ -164,7 +302,8 @@ pub struct Send {
     #[structopt(value_name = "dBm", short = "o", long = "power", default_value = "0")]
     pub power: i8,
 
-    /// Spreading factor [7,8,9,10,11,12].
+    /// Spreading factor [5,6,7,8,9,10,11,12]. 5 and 6 require an
+    /// SX1302.
     #[structopt(
         value_name = "SF",
         short = "s",
@@ -207,9 +346,23 @@ pub struct Serve {
     #[structopt(short = "p", parse(from_occurrences))]
     pub print_level: u8,
 
+    /// Config file, reloaded on SIGHUP.
     #[structopt(short = "c", long = "config", parse(from_os_str))]
     pub cfg_file: Option<PathBuf>,
 
+    /// Concentrator backend to serve [hw,sim]. `sim` requires no
+    /// hardware and generates synthetic uplink traffic.
+    #[structopt(
+        long = "backend",
+        default_value = "hw",
+        raw(possible_values = r#"&["hw", "sim"]"#)
+    )]
+    pub backend: Backend,
+
+    /// Concentrator chip [sx1301,sx1302]. Overrides `[board] chip`.
+    #[structopt(long = "chip", raw(possible_values = r#"&["sx1301", "sx1302"]"#))]
+    pub chip: Option<loragw::Chip>,
+
     /// Address on which to listen for requests.
     #[structopt(
         value_name = "ADDR",
@@ -20,6 +20,11 @@ quick_error! {
             from()
             description(err.description())
         }
+        /// Configuration failed validation, with each problem found.
+        Invalid(problems: Vec<String>) {
+            description("invalid configuration")
+            display("invalid configuration:\n  {}", problems.join("\n  "))
+        }
         /// Catch-all error.
         Generic(err: String) {
             from()
@@ -42,12 +42,14 @@ fn go(cmd: cmdline::Cmd) -> AppResult {
     use crate::cmdline::Cmd::*;
 
     match cmd {
-        Bist => app::built_in_self_test(),
-        Connect => app::connect(),
+        Bist(args) => app::built_in_self_test(args),
+        Connect(args) => app::connect(args),
         Listen(args) => app::listen(args),
         LongFi(args) => app::longfi(args),
         LongFiTest(args) => app::longfi_test(args),
         Send(args) => app::send(args),
         Serve(args) => app::serve(args),
+        Reset(args) => app::reset(args),
+        Config(args) => app::config(args),
     }
 }